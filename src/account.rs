@@ -1,17 +1,18 @@
 use crate::block;
 use crate::encoding;
+use crate::network::Network;
 use crate::work;
 use crate::raw;
 use bitvec::prelude::*;
 use byteorder::{BigEndian, ByteOrder};
 use bigdecimal::BigDecimal;
-use ed25519_dalek_blake2b::{Keypair, PublicKey, SecretKey, Signer};
+use ed25519_dalek_blake2b::{Keypair, PublicKey, SecretKey, Signature, Signer, Verifier};
 use regex::Regex;
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
 use std::error::Error;
 
 
-const DEFUALT_REP: &str = "nano_1center16ci77qw5w69ww8sy4i4bfmgfhr81ydzpurm91cauj11jn6y3uc5y";
+pub const DEFAULT_REP: &str = "nano_1center16ci77qw5w69ww8sy4i4bfmgfhr81ydzpurm91cauj11jn6y3uc5y";
 
 pub struct Account {
     pub index: u32,
@@ -20,6 +21,7 @@ pub struct Account {
     pub frontier: [u8; 32],
     pub rep: String,
     pub pk: [u8; 32],
+    pub network: Network,
     kp: Keypair,
     work_cache: work::WorkStatus,
 }
@@ -32,15 +34,20 @@ pub struct AccountInfo {
 }
 
 impl Account {
-    pub fn new(index: u32, seed: &[u8; 32]) -> Result<Account, Box<dyn Error>> {
+    pub fn new(index: u32, seed: &[u8; 32], network: Network) -> Result<Account, Box<dyn Error>> {
         let sk = Account::create_sk(&index, seed).unwrap();
-        let pk = Account::create_pk(&sk).unwrap();
+        Account::from_key(index, &sk, network)
+    }
+
+    // an account from its private key rather than a seed, eg. the dev genesis
+    pub fn from_key(index: u32, sk: &[u8; 32], network: Network) -> Result<Account, Box<dyn Error>> {
+        let pk = Account::create_pk(sk).unwrap();
         let kp = Keypair {
-            secret: SecretKey::from_bytes(&sk).map_err(|e| format!("{}", e))?,
+            secret: SecretKey::from_bytes(sk).map_err(|e| format!("{}", e))?,
             public: PublicKey::from_bytes(&pk).map_err(|e| format!("{}", e))?,
         };
         let addr = Account::create_addr(&pk).unwrap();
-        let (frontier, rep, balance) = ([0u8; 32], String::from(DEFUALT_REP), raw::Raw::new(0));
+        let (frontier, rep, balance) = ([0u8; 32], String::from(DEFAULT_REP), raw::Raw::new(0));
         Ok(Account {
             index,
             addr,
@@ -48,6 +55,7 @@ impl Account {
             frontier,
             rep,
             pk,
+            network,
             kp,
             work_cache: work::WorkStatus::Empty,
         })
@@ -81,7 +89,9 @@ impl Account {
             return Err("representative is not a valid nano address".into());
         }
         self.rep = String::from(rep);
-        Ok(self.create_block(self.balance, "0", subtype)?)
+        // change blocks have no link, which the node expects as zeros
+        let link = hex::encode_upper([0u8; 32]);
+        Ok(self.create_block(self.balance, &link, subtype)?)
     }
 
     pub fn load(&mut self, balance: raw::Raw, frontier: String, rep: String) {
@@ -112,6 +122,17 @@ impl Account {
         matches!(self.work_cache, work::WorkStatus::Finished(_))
     }
 
+    pub fn cached_work(&self) -> Option<&str> {
+        match &self.work_cache {
+            work::WorkStatus::Finished(w) => Some(w),
+            _ => None,
+        }
+    }
+
+    pub fn clear_work(&mut self) {
+        self.work_cache = work::WorkStatus::Empty;
+    }
+
     //https://docs.nano.org/integration-guides/the-basics/#seed
     fn create_sk(index: &u32, seed: &[u8; 32]) -> Result<[u8; 32], Box<dyn Error>> {
         let mut i_buf = [0; 4];
@@ -196,6 +217,18 @@ pub fn decode_addr(addr: &str) -> Result<[u8; 32], Box<dyn Error>> {
     Ok(addr_bytes)
 }
 
+pub fn encode_addr(pk: &[u8; 32]) -> Result<String, Box<dyn Error>> {
+    Account::create_addr(pk)
+}
+
+// checks a block hash was signed by the key behind addr
+pub fn verify_signature(addr: &str, hash: &str, signature: &str) -> Result<bool, Box<dyn Error>> {
+    let pk = PublicKey::from_bytes(&decode_addr(addr)?).map_err(|e| format!("{}", e))?;
+    let sig =
+        Signature::try_from(hex::decode(signature)?.as_slice()).map_err(|e| format!("{}", e))?;
+    Ok(pk.verify(&hex::decode(hash)?, &sig).is_ok())
+}
+
 pub fn valid_addr(addr: &str) -> bool {
     // todo: validate with checksum
     let re = Regex::new(r"^(nano|xrb)_[13]{1}[13456789abcdefghijkmnopqrstuwxyz]{59}$").unwrap();
@@ -258,4 +291,14 @@ mod tests {
             hex::encode_upper(&pk)
         );
     }
+
+    #[test]
+    fn verifies_signatures() {
+        let a = Account::new(0, &TEST_SEED, Network::Dev).unwrap();
+        let hash = hex::encode([7u8; 32]);
+        let sig = hex::encode(a.kp.sign(&[7u8; 32]).to_bytes());
+        assert!(verify_signature(&a.addr, &hash, &sig).unwrap());
+        let other = Account::new(1, &TEST_SEED, Network::Dev).unwrap();
+        assert!(!verify_signature(&other.addr, &hash, &sig).unwrap());
+    }
 }
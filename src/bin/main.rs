@@ -1,28 +1,649 @@
+use bigdecimal::BigDecimal;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 use dialoguer::{theme::ColorfulTheme, Input, Password, Select};
 use nanors::account;
+use nanors::config;
 use nanors::manager;
+use nanors::network;
 use nanors::wallet;
-use std::fs::OpenOptions;
-use std::io::{prelude::*, BufReader};
-use bigdecimal::BigDecimal;
+use nanors::work;
+use nanors::work_server;
+use serde_json::json;
+use std::env;
+use std::fs;
+use tokio::sync::broadcast;
+
+const PASSWORD_ENV: &str = "NANORS_PASSWORD";
+
+// exit codes for scripted use
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_WALLET: i32 = 3;
+const EXIT_AUTH: i32 = 4;
+const EXIT_NODE: i32 = 5;
+const EXIT_INPUT: i32 = 6;
+
+#[derive(Debug)]
+enum CliError {
+    Usage(String),
+    Wallet(String),
+    Auth(String),
+    Node(String),
+    Input(String),
+    Other(String),
+}
+
+impl CliError {
+    // wallet errors carry their own kind, anything else is classed by the caller
+    fn from_err(e: Box<dyn std::error::Error>, fallback: fn(String) -> CliError) -> CliError {
+        match e.downcast_ref::<wallet::WalletError>() {
+            Some(wallet::WalletError::BadPassword) => CliError::Auth(e.to_string()),
+            Some(_) => CliError::Wallet(e.to_string()),
+            None => fallback(e.to_string()),
+        }
+    }
+
+    fn code(&self) -> i32 {
+        match self {
+            CliError::Usage(_) => EXIT_USAGE,
+            CliError::Wallet(_) => EXIT_WALLET,
+            CliError::Auth(_) => EXIT_AUTH,
+            CliError::Node(_) => EXIT_NODE,
+            CliError::Input(_) => EXIT_INPUT,
+            CliError::Other(_) => EXIT_FAILURE,
+        }
+    }
+
+    fn kind(&self) -> &str {
+        match self {
+            CliError::Usage(_) => "usage",
+            CliError::Wallet(_) => "wallet",
+            CliError::Auth(_) => "auth",
+            CliError::Node(_) => "node",
+            CliError::Input(_) => "input",
+            CliError::Other(_) => "other",
+        }
+    }
+
+    fn msg(&self) -> &str {
+        match self {
+            CliError::Usage(m)
+            | CliError::Wallet(m)
+            | CliError::Auth(m)
+            | CliError::Node(m)
+            | CliError::Input(m)
+            | CliError::Other(m) => m,
+        }
+    }
+}
+
+struct CmdOutput {
+    text: Vec<String>,
+    json: serde_json::Value,
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let main_menu = &["wallet", "exit"];
+    let wallet_arg = Arg::with_name("wallet")
+        .long("wallet")
+        .value_name("NAME")
+        .required(true);
+    let app = App::new("nanors")
+        .about("a simple nano wallet. runs the interactive menu when no subcommand is given.")
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .global(true)
+                .help("print results and errors as json"),
+        )
+        .arg(
+            Arg::with_name("password-file")
+                .long("password-file")
+                .value_name("PATH")
+                .global(true)
+                .help("read the wallet password from a file"),
+        )
+        .arg(
+            Arg::with_name("password-stdin")
+                .long("password-stdin")
+                .global(true)
+                .help("read the wallet password from stdin. NANORS_PASSWORD is used otherwise"),
+        )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .value_name("PATH")
+                .global(true)
+                .help("config file. defaults to nanors.toml when present"),
+        )
+        .arg(
+            Arg::with_name("network")
+                .long("network")
+                .value_name("NETWORK")
+                .possible_values(&["live", "beta", "dev"])
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("rpc-url")
+                .long("rpc-url")
+                .value_name("URL")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("ws-url")
+                .long("ws-url")
+                .value_name("URL")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("work")
+                .long("work")
+                .value_name("SOURCE")
+                .possible_values(&["node", "local"])
+                .global(true)
+                .help("generate work with the node (or --work-url) or locally"),
+        )
+        .arg(
+            Arg::with_name("work-url")
+                .long("work-url")
+                .value_name("URL")
+                .global(true)
+                .help("a work server to use instead of the rpc node"),
+        )
+        .subcommand(
+            SubCommand::with_name("wallet")
+                .about("manage local wallets")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("create")
+                        .about("create a new wallet with a random seed")
+                        .arg(wallet_arg.clone()),
+                )
+                .subcommand(SubCommand::with_name("list").about("list local wallet names"))
+                .subcommand(
+                    SubCommand::with_name("show")
+                        .about("show a wallet and its accounts")
+                        .arg(wallet_arg.clone()),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("account")
+                .about("manage the accounts of a wallet")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("add")
+                        .about("derive the next account from the wallet seed")
+                        .arg(wallet_arg.clone()),
+                )
+                .subcommand(
+                    SubCommand::with_name("list")
+                        .about("list wallet accounts")
+                        .arg(wallet_arg.clone()),
+                )
+                .subcommand(
+                    SubCommand::with_name("balance")
+                        .about("sync with the node (receiving pending blocks) and show balances")
+                        .arg(wallet_arg.clone())
+                        .arg(Arg::with_name("account").long("account").value_name("ADDR")),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("send")
+                .about("send nano from a wallet account")
+                .arg(wallet_arg.clone())
+                .arg(
+                    Arg::with_name("from")
+                        .long("from")
+                        .value_name("ADDR")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("to")
+                        .long("to")
+                        .value_name("ADDR")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("amount")
+                        .long("amount")
+                        .value_name("MNANO")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("confirm")
+                        .long("confirm")
+                        .help("wait until the network confirms the send"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("change")
+                .about("change the representative of a wallet account")
+                .arg(wallet_arg)
+                .arg(
+                    Arg::with_name("account")
+                        .long("account")
+                        .value_name("ADDR")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("rep")
+                        .long("rep")
+                        .value_name("ADDR")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("work-server")
+                .about("serve work_generate, work_validate and work_cancel over http")
+                .arg(
+                    Arg::with_name("listen")
+                        .long("listen")
+                        .value_name("ADDR")
+                        .default_value(work_server::DEFAULT_WORK_SERVER_ADDR),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("bench")
+                .about("benchmark local proof of work against random roots and verify each result")
+                .arg(
+                    Arg::with_name("runs")
+                        .long("runs")
+                        .value_name("N")
+                        .default_value("10"),
+                )
+                .arg(
+                    Arg::with_name("threads")
+                        .long("threads")
+                        .value_name("N")
+                        .multiple(true)
+                        .use_delimiter(true),
+                )
+                .arg(
+                    Arg::with_name("difficulty")
+                        .long("difficulty")
+                        .value_name("HEX")
+                        .multiple(true)
+                        .use_delimiter(true),
+                ),
+        );
+    let matches = match app.get_matches_safe() {
+        Ok(m) => m,
+        Err(e) if e.use_stderr() => {
+            eprintln!("{}", e.message);
+            std::process::exit(EXIT_USAGE);
+        }
+        Err(e) => e.exit(),
+    };
+    match matches.subcommand() {
+        ("work-server", Some(m)) => run_work_server(m).await,
+        ("bench", Some(m)) => run_bench(m).await,
+        (_, None) => match load_config(&matches) {
+            Ok(config) => run_interactive(config).await,
+            Err(e) => {
+                print_cli_err(&e, false);
+                std::process::exit(e.code());
+            }
+        },
+        (cmd, Some(m)) => {
+            let (res, leaf) = match (cmd, m.subcommand()) {
+                ("wallet", (sub, Some(sm))) => (run_wallet_cmd(sub, sm).await, sm),
+                ("account", (sub, Some(sm))) => (run_account_cmd(sub, sm).await, sm),
+                ("send", _) => (run_send(m).await, m),
+                ("change", _) => (run_change(m).await, m),
+                _ => (Err(CliError::Usage(format!("{} unrecognized", cmd))), m),
+            };
+            let json = leaf.is_present("json");
+            match res {
+                Ok(out) => print_output(out, json),
+                Err(e) => {
+                    print_cli_err(&e, json);
+                    std::process::exit(e.code());
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+fn print_output(out: CmdOutput, json: bool) {
+    if json {
+        println!("{}", out.json);
+    } else {
+        out.text.iter().for_each(|l| print_show(l));
+    }
+}
+
+fn print_cli_err(e: &CliError, json: bool) {
+    if json {
+        eprintln!("{}", json!({ "error": e.msg(), "kind": e.kind() }));
+    } else {
+        print_err(e.msg());
+    }
+}
+
+// password from --password-stdin, --password-file or the environment, in that order.
+// falls back to a prompt when someone is at the terminal.
+fn read_password(m: &ArgMatches<'_>, confirm: bool) -> Result<String, CliError> {
+    let trim = |pw: String| pw.trim_end_matches(&['\r', '\n'][..]).to_string();
+    if m.is_present("password-stdin") {
+        let mut pw = String::new();
+        std::io::stdin()
+            .read_line(&mut pw)
+            .map_err(|e| CliError::Usage(format!("could not read password: {}", e)))?;
+        return Ok(trim(pw));
+    }
+    if let Some(path) = m.value_of("password-file") {
+        let pw = fs::read_to_string(path)
+            .map_err(|e| CliError::Usage(format!("could not read password file: {}", e)))?;
+        return Ok(trim(pw));
+    }
+    if let Ok(pw) = env::var(PASSWORD_ENV) {
+        return Ok(pw);
+    }
+    if console::user_attended() {
+        let theme = ColorfulTheme::default();
+        let mut prompt = Password::with_theme(&theme);
+        prompt.with_prompt("password");
+        if confirm {
+            prompt.with_confirmation("repeat password", "error: the passwords don't match.");
+        }
+        return prompt
+            .interact()
+            .map_err(|e| CliError::Usage(format!("could not read password: {}", e)));
+    }
+    Err(CliError::Usage(format!(
+        "no password given. use --password-stdin, --password-file or {}",
+        PASSWORD_ENV
+    )))
+}
+
+fn load_config(m: &ArgMatches<'_>) -> Result<config::ManagerConfig, CliError> {
+    let flags = config::ConfigOverrides {
+        network: m.value_of("network").map(|n| n.parse().unwrap()),
+        rpc_url: m.value_of("rpc-url").map(String::from),
+        ws_url: m.value_of("ws-url").map(String::from),
+        work: m.value_of("work").map(|w| w.parse().unwrap()),
+        work_url: m.value_of("work-url").map(String::from),
+        ..Default::default()
+    };
+    config::ManagerConfig::load(m.value_of("config"), flags)
+        .map_err(|e| CliError::Usage(format!("bad config: {}", e)))
+}
+
+fn load_wallet(m: &ArgMatches<'_>, pw: &str) -> Result<wallet::Wallet, CliError> {
+    let name = m.value_of("wallet").unwrap();
+    let network = load_config(m)?.network;
+    wallet::Wallet::load(name, pw, network).map_err(|e| CliError::from_err(e, CliError::Other))
+}
+
+async fn load_manager(m: &ArgMatches<'_>) -> Result<manager::Manager, CliError> {
+    let config = load_config(m)?;
+    let w = load_wallet(m, &read_password(m, false)?)?;
+    let mut manager = manager::Manager::new(config)
+        .await
+        .map_err(|e| CliError::Node(e.to_string()))?;
+    print_events(manager.events(), false, m.is_present("json"));
+    manager
+        .check_node()
+        .await
+        .map_err(|e| CliError::Node(e.to_string()))?;
+    manager
+        .set_wallet(w)
+        .await
+        .map_err(|e| CliError::Node(e.to_string()))?;
+    Ok(manager)
+}
+
+async fn wallet_accounts(w: &wallet::Wallet) -> (Vec<String>, serde_json::Value) {
+    let accounts = w.accounts.lock().await;
+    let text = accounts
+        .iter()
+        .map(|a| format!("  {} : {}", a.index, a.addr))
+        .collect();
+    let json = accounts
+        .iter()
+        .map(|a| json!({ "index": a.index, "addr": a.addr }))
+        .collect();
+    (text, json)
+}
+
+async fn run_wallet_cmd(sub: &str, m: &ArgMatches<'_>) -> Result<CmdOutput, CliError> {
+    match sub {
+        "create" => {
+            let name = m.value_of("wallet").unwrap();
+            let network = load_config(m)?.network;
+            let pw = read_password(m, true)?;
+            let w = wallet::Wallet::new(name, &pw, network)
+                .await
+                .map_err(|e| CliError::from_err(e, CliError::Other))?;
+            let (mut text, accounts) = wallet_accounts(&w).await;
+            text.insert(0, format!("created wallet {}", w.name));
+            Ok(CmdOutput {
+                text,
+                json: json!({ "wallet": w.name, "accounts": accounts }),
+            })
+        }
+        "list" => {
+            let network = load_config(m)?.network;
+            let names =
+                wallet::list_local_wallets(network).map_err(|e| CliError::Other(e.to_string()))?;
+            Ok(CmdOutput {
+                text: names.iter().map(|n| format!("  {}", n)).collect(),
+                json: json!({ "wallets": names }),
+            })
+        }
+        "show" => {
+            let w = load_wallet(m, &read_password(m, false)?)?;
+            let (mut text, accounts) = wallet_accounts(&w).await;
+            text.insert(0, format!("{} ({} accounts)", w.name, text.len()));
+            Ok(CmdOutput {
+                text,
+                json: json!({ "wallet": w.name, "accounts": accounts }),
+            })
+        }
+        _ => Err(CliError::Usage(format!("wallet {} unrecognized", sub))),
+    }
+}
+
+async fn run_account_cmd(sub: &str, m: &ArgMatches<'_>) -> Result<CmdOutput, CliError> {
+    match sub {
+        "add" => {
+            let pw = read_password(m, false)?;
+            let mut w = load_wallet(m, &pw)?;
+            w.add_account(&pw)
+                .await
+                .map_err(|e| CliError::from_err(e, CliError::Other))?;
+            let accounts = w.accounts.lock().await;
+            let a = accounts.last().unwrap();
+            Ok(CmdOutput {
+                text: vec![format!("  {} : {}", a.index, a.addr)],
+                json: json!({ "index": a.index, "addr": a.addr }),
+            })
+        }
+        "list" => {
+            let w = load_wallet(m, &read_password(m, false)?)?;
+            let (text, accounts) = wallet_accounts(&w).await;
+            Ok(CmdOutput {
+                text,
+                json: json!({ "accounts": accounts }),
+            })
+        }
+        "balance" => {
+            let manager = load_manager(m).await?;
+            let mut infos = manager.get_accounts_info().await;
+            if let Some(addr) = m.value_of("account") {
+                infos.retain(|a| a.addr == addr);
+                if infos.is_empty() {
+                    return Err(CliError::Input(format!("{} is not in this wallet", addr)));
+                }
+            }
+            Ok(CmdOutput {
+                text: infos
+                    .iter()
+                    .map(|a| format!("  {} : {} : {}", a.index, a.addr, a.balance_mnano))
+                    .collect(),
+                json: json!({
+                    "accounts": infos
+                        .iter()
+                        .map(|a| json!({
+                            "index": a.index,
+                            "addr": a.addr,
+                            "balance": a.balance_mnano.to_string(),
+                        }))
+                        .collect::<Vec<_>>()
+                }),
+            })
+        }
+        _ => Err(CliError::Usage(format!("account {} unrecognized", sub))),
+    }
+}
+
+async fn run_send(m: &ArgMatches<'_>) -> Result<CmdOutput, CliError> {
+    let (from, to) = (m.value_of("from").unwrap(), m.value_of("to").unwrap());
+    if !account::valid_addr(to) {
+        return Err(CliError::Input(format!(
+            "{} is not a valid nano address",
+            to
+        )));
+    }
+    let amount: BigDecimal = m
+        .value_of("amount")
+        .unwrap()
+        .parse()
+        .map_err(|_| CliError::Input(String::from("cannot parse this amount")))?;
+    if amount <= BigDecimal::from(0) {
+        return Err(CliError::Input(String::from("amount must be positive")));
+    }
+    let manager = load_manager(m).await?;
+    let from_info = manager
+        .get_accounts_info()
+        .await
+        .into_iter()
+        .find(|a| a.addr == from)
+        .ok_or_else(|| CliError::Input(format!("{} is not in this wallet", from)))?;
+    if amount > from_info.balance_mnano {
+        return Err(CliError::Input(String::from("insufficient balance")));
+    }
+    let hash = manager
+        .send(amount.clone(), from, to)
+        .await
+        .map_err(|e| CliError::Node(e.to_string()))?;
+    let mut text = vec![format!("  success. block hash: {}", hash)];
+    let confirmed = m.is_present("confirm");
+    if confirmed {
+        manager
+            .confirmation(&hash, nanors::manager::ConfirmOptions::default())
+            .await
+            .map_err(|e| CliError::Node(e.to_string()))?;
+        text.push(String::from("  confirmed."));
+    }
+    Ok(CmdOutput {
+        text,
+        json: json!({
+            "hash": hash,
+            "from": from,
+            "to": to,
+            "amount": amount.to_string(),
+            "confirmed": confirmed,
+        }),
+    })
+}
+
+async fn run_change(m: &ArgMatches<'_>) -> Result<CmdOutput, CliError> {
+    let (acct, rep) = (m.value_of("account").unwrap(), m.value_of("rep").unwrap());
+    if !account::valid_addr(rep) {
+        return Err(CliError::Input(format!(
+            "{} is not a valid nano address",
+            rep
+        )));
+    }
+    let manager = load_manager(m).await?;
+    if !manager
+        .get_accounts_info()
+        .await
+        .iter()
+        .any(|a| a.addr == acct)
+    {
+        return Err(CliError::Input(format!("{} is not in this wallet", acct)));
+    }
+    let hash = manager
+        .change(acct, rep)
+        .await
+        .map_err(|e| CliError::Node(e.to_string()))?;
+    Ok(CmdOutput {
+        text: vec![format!("  success. block hash: {}", hash)],
+        json: json!({ "hash": hash, "account": acct, "representative": rep }),
+    })
+}
+
+async fn run_work_server(m: &ArgMatches<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    let addr = m.value_of("listen").unwrap().parse()?;
+    let network = load_config(m).map_err(|e| e.msg().to_string())?.network;
+    print_italic(&format!(
+        "\n\n  nanors work server ({}) listening on {}\n\n",
+        network, addr
+    ));
+    work_server::serve(addr, network).await
+}
+
+async fn run_bench(m: &ArgMatches<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    let runs: usize = m.value_of("runs").unwrap().parse()?;
+    let threads: Vec<u64> = match m.values_of("threads") {
+        Some(t) => t.map(|t| t.parse()).collect::<Result<_, _>>()?,
+        None => vec![1, work::POW_LOCAL_WORKERS],
+    };
+    let difficulties: Vec<String> = match m.values_of("difficulty") {
+        Some(d) => d.map(String::from).collect(),
+        None => vec![
+            String::from(work::DEFAULT_DIFFICULTY),
+            String::from(work::RECV_DIFFICULTY),
+        ],
+    };
+    let mut invalid = 0;
+    for difficulty in &difficulties {
+        for &t in &threads {
+            let d = difficulty.clone();
+            let b = tokio::task::spawn_blocking(move || {
+                work::benchmark(runs, &d, t).map_err(|e| e.to_string())
+            })
+            .await??;
+            invalid += b.invalid;
+            print_show(&format!(
+                "  difficulty {} | threads {} | runs {} | mean {:.3}s | p95 {:.3}s | {:.2} MH/s | invalid {}",
+                b.difficulty,
+                b.threads,
+                b.runs,
+                b.mean.as_secs_f64(),
+                b.p95.as_secs_f64(),
+                b.hashes_per_sec / 1_000_000.0,
+                b.invalid
+            ));
+        }
+    }
+    if invalid > 0 {
+        return Err(format!("{} runs produced invalid work", invalid).into());
+    }
+    Ok(())
+}
+
+async fn run_interactive(config: config::ManagerConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let main_menu = &["wallet", "node", "exit"];
     let wallet_menu = &["new", "load", "show", "back"];
     print_italic("\n\n  nanors   \n\n");
 
-    let mut m = match manager::Manager::new().await {
+    let mut m = match manager::Manager::new(config).await {
         Ok(m) => m,
         Err(e) => {
             print_err(&format!("could not initalize manager: {:?}", e));
             return Ok(());
         }
     };
+    print_events(m.events(), true, false);
     loop {
         let selection = menu_select(main_menu, "sub-menu:");
         match selection {
             "wallet" => run_wallet_menu(wallet_menu, &mut m).await,
+            "node" => run_node_menu(&mut m).await,
             "exit" => break,
             _ => print_err(&format!("{} unrecognized", selection)),
         }
@@ -31,6 +652,53 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+async fn run_node_menu(manager: &mut manager::Manager) {
+    let config = manager.config();
+    print_show(&format!(
+        "\n  network: {}\n  rpc: {}\n  ws: {}\n",
+        config.network, config.rpc_url, config.ws_url
+    ));
+    for h in manager.node_health() {
+        let latency = h
+            .latency
+            .map_or(String::from("-"), |l| format!("{}ms", l.as_millis()));
+        let blocks = h.block_count.map_or(String::from("-"), |b| b.to_string());
+        print_show_dim(&format!(
+            "  {} latency: {} failures: {} blocks: {}",
+            h.url, latency, h.consecutive_failures, blocks
+        ));
+    }
+    let mut stats: Vec<_> = manager.node_stats().into_iter().collect();
+    stats.sort_by(|a, b| a.0.cmp(&b.0));
+    for (action, s) in stats {
+        print_show_dim(&format!(
+            "  {}: {} requests, {} failed, {} rate limited",
+            action, s.requests, s.failed, s.rate_limited
+        ));
+    }
+    let (rpc_url, ws_url) = (config.rpc_url.clone(), config.ws_url.clone());
+    let rpc_url = url_prompt("rpc node url:", rpc_url);
+    let ws_url = url_prompt("websocket node url:", ws_url);
+    match manager.set_node(&rpc_url, &ws_url).await {
+        Ok(()) => print_show(&format!("\n  using {}\n", rpc_url)),
+        Err(e) => print_err(&format!("\n{}\n", e)),
+    }
+}
+
+fn url_prompt(prompt: &str, default: String) -> String {
+    Input::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .default(default)
+        .validate_with(|input: &String| -> Result<(), &str> {
+            match url::Url::parse(input) {
+                Ok(_) => Ok(()),
+                Err(_) => Err("not a valid url"),
+            }
+        })
+        .interact()
+        .unwrap()
+}
+
 fn print_err(msg: &str) {
     eprintln!("{}", console::style(msg).red());
 }
@@ -39,8 +707,33 @@ fn print_show(msg: &str) {
     println!("{}", console::style(msg).yellow());
 }
 
-fn print_under(msg: &str) {
-    println!("{}", console::style(msg).underlined());
+// background errors and warnings from the manager, and when interactive what
+// happened to the accounts meanwhile. with --json they are json lines on stderr,
+// like errors.
+fn print_events(mut events: broadcast::Receiver<manager::Event>, notify: bool, json: bool) {
+    tokio::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(manager::Event::Error(e)) if json => eprintln!("{}", json!({ "error": e })),
+                Ok(manager::Event::Warning(w)) if json => {
+                    eprintln!("{}", json!({ "warning": w }))
+                }
+                Ok(e @ manager::Event::Error(_)) | Ok(e @ manager::Event::Warning(_)) => {
+                    print_err(&format!("\n{}", e))
+                }
+                Ok(e @ manager::Event::IncomingPending { .. })
+                | Ok(e @ manager::Event::Received { .. })
+                | Ok(e @ manager::Event::ConnectionLost(_))
+                | Ok(e @ manager::Event::ConnectionRestored)
+                    if notify =>
+                {
+                    print_show_dim(&format!("\n{}", e))
+                }
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
 }
 
 fn print_show_dim(msg: &str) {
@@ -65,7 +758,7 @@ async fn run_wallet_menu(menu: &[&str], manager: &mut manager::Manager) {
         let selection = menu_select(menu, "wallet options:");
         match selection {
             "new" => {
-                let w = wallet_init(true).await;
+                let w = wallet_init(false, manager.config().network).await;
                 if let Err(e) = w {
                     print_err(&format!("\n{}\n", e));
                     continue;
@@ -77,7 +770,7 @@ async fn run_wallet_menu(menu: &[&str], manager: &mut manager::Manager) {
                 run_account_menu(manager).await;
             }
             "load" => {
-                let w = wallet_init(true).await;
+                let w = wallet_init(true, manager.config().network).await;
                 if let Err(e) = w {
                     print_err(&format!("\n{}\n", e));
                     continue;
@@ -88,21 +781,24 @@ async fn run_wallet_menu(menu: &[&str], manager: &mut manager::Manager) {
                 }
                 run_account_menu(manager).await;
             }
-            "show" => wallets_show(),
+            "show" => wallets_show(manager.config().network),
             "back" => break,
             _ => print_err(&format!("unrecognized command {}", selection)),
         }
     }
 }
 
-async fn wallet_init(load: bool) -> Result<wallet::Wallet, Box<dyn std::error::Error>> {
+async fn wallet_init(
+    load: bool,
+    network: network::Network,
+) -> Result<wallet::Wallet, Box<dyn std::error::Error>> {
     let w: Result<wallet::Wallet, Box<dyn std::error::Error>>;
     if load {
         let (name, password) = wallet_prompt(false);
-        w = wallet::Wallet::load(&name, &password);
+        w = wallet::Wallet::load(&name, &password, network);
     } else {
         let (name, password) = wallet_prompt(true);
-        w = wallet::Wallet::new(&name, &password).await;
+        w = wallet::Wallet::new(&name, &password, network).await;
     }
     w
 }
@@ -136,19 +832,15 @@ fn wallet_prompt(confirm_pass: bool) -> (String, String) {
     (name, password)
 }
 
-fn wallets_show() {
-    if let Ok(file) = OpenOptions::new().read(true).open(wallet::WALLET_FILE_PATH) {
-        let reader = BufReader::new(file);
-        println!();
-        for line in reader.lines() {
-            let line = line.unwrap();
-            if let Some(name) = line.split("|").next() {
-                print_show(&format!("  {}", name));
-            }
+fn wallets_show(network: network::Network) {
+    match wallet::list_local_wallets(network) {
+        Ok(names) if !names.is_empty() => {
+            println!();
+            names.iter().for_each(|n| print_show(&format!("  {}", n)));
+            println!();
         }
-        println!();
-    } else {
-        print_err("\nwallet file not found\n");
+        Ok(_) => print_err("\nwallet file not found\n"),
+        Err(e) => print_err(&format!("\n{}\n", e)),
     }
 }
 
@@ -31,6 +31,7 @@ pub enum SubType {
     Open,
     Receive,
     Change,
+    Epoch,
 }
 // todo: need string rep for serializing to process req.
 
@@ -70,7 +71,7 @@ impl NanoBlock {
         let bal: [u8; 16] = self.balance.parse::<u128>()?.to_be_bytes();
         let link = match self.subtype {
             Some(SubType::Send) => account::decode_addr(&self.link)?,
-            Some(SubType::Receive) | Some(SubType::Open) => {
+            Some(SubType::Receive) | Some(SubType::Open) | Some(SubType::Epoch) => {
                 hex::decode(&self.link)?[..].try_into()?
             }
             Some(SubType::Change) => [0u8; 32],
@@ -0,0 +1,310 @@
+// manager configuration. defaults come from the network, then the config file,
+// then NANORS_* environment variables, then command line flags.
+use crate::account;
+use crate::network::Network;
+use crate::rpc::EndpointOptions;
+use crate::ws;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::time::Duration;
+
+pub const CONFIG_FILE_PATH: &str = "nanors.toml";
+const DEFAULT_RPC_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_WORK_TIMEOUT_SECS: u64 = 120;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WorkSource {
+    // work_generate on work_url when set, then on the rpc nodes
+    Node,
+    // work::pow_local
+    Local,
+}
+
+impl std::str::FromStr for WorkSource {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "node" => Ok(WorkSource::Node),
+            "local" => Ok(WorkSource::Local),
+            _ => Err(format!("unknown work source {}. expected node or local", s)),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ManagerConfig {
+    pub network: Network,
+    pub rpc_url: String,
+    // tried in order when rpc_url fails or falls behind
+    pub fallback_rpc_urls: Vec<String>,
+    // when set, account and block info is read from every rpc node and only
+    // trusted if at least this many answered and a majority of them agree
+    pub quorum: Option<usize>,
+    pub ws_url: String,
+    pub work: WorkSource,
+    // a separate work_generate provider, eg. a nano-work-server or `nanors work-server`
+    pub work_url: Option<String>,
+    pub default_rep: String,
+    pub rpc_timeout_secs: u64,
+    pub work_timeout_secs: u64,
+    // reconnect the websocket when the node sent nothing, not even a pong, for this long
+    pub ws_timeout_secs: u64,
+    // connection settings (auth, proxy, certificates..) keyed by rpc or work url
+    pub endpoints: HashMap<String, EndpointOptions>,
+}
+
+// one layer of settings. unset fields fall through to the layer below.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigOverrides {
+    pub network: Option<Network>,
+    pub rpc_url: Option<String>,
+    pub fallback_rpc_urls: Option<Vec<String>>,
+    pub quorum: Option<usize>,
+    pub ws_url: Option<String>,
+    pub work: Option<WorkSource>,
+    pub work_url: Option<String>,
+    pub default_rep: Option<String>,
+    pub rpc_timeout_secs: Option<u64>,
+    pub work_timeout_secs: Option<u64>,
+    pub ws_timeout_secs: Option<u64>,
+    pub endpoints: Option<HashMap<String, EndpointOptions>>,
+}
+
+impl ConfigOverrides {
+    pub fn from_file(path: &str) -> Result<ConfigOverrides, Box<dyn Error>> {
+        let s = fs::read_to_string(path)?;
+        toml::from_str(&s).map_err(|e| format!("config {}: {}", path, e).into())
+    }
+
+    pub fn from_env() -> Result<ConfigOverrides, Box<dyn Error>> {
+        let var = |k: &str| env::var(k).ok();
+        Ok(ConfigOverrides {
+            network: var("NANORS_NETWORK").map(|n| n.parse()).transpose()?,
+            rpc_url: var("NANORS_RPC_URL"),
+            // comma separated
+            fallback_rpc_urls: var("NANORS_FALLBACK_RPC_URLS")
+                .map(|u| u.split(',').map(|u| String::from(u.trim())).collect()),
+            quorum: var("NANORS_QUORUM").map(|q| q.parse()).transpose()?,
+            ws_url: var("NANORS_WS_URL"),
+            work: var("NANORS_WORK").map(|w| w.parse()).transpose()?,
+            work_url: var("NANORS_WORK_URL"),
+            default_rep: var("NANORS_DEFAULT_REP"),
+            rpc_timeout_secs: var("NANORS_RPC_TIMEOUT_SECS")
+                .map(|t| t.parse())
+                .transpose()?,
+            work_timeout_secs: var("NANORS_WORK_TIMEOUT_SECS")
+                .map(|t| t.parse())
+                .transpose()?,
+            ws_timeout_secs: var("NANORS_WS_TIMEOUT_SECS")
+                .map(|t| t.parse())
+                .transpose()?,
+            endpoints: None,
+        })
+    }
+
+    // fields set in `over` win
+    pub fn merge(self, over: ConfigOverrides) -> ConfigOverrides {
+        ConfigOverrides {
+            network: over.network.or(self.network),
+            rpc_url: over.rpc_url.or(self.rpc_url),
+            fallback_rpc_urls: over.fallback_rpc_urls.or(self.fallback_rpc_urls),
+            quorum: over.quorum.or(self.quorum),
+            ws_url: over.ws_url.or(self.ws_url),
+            work: over.work.or(self.work),
+            work_url: over.work_url.or(self.work_url),
+            default_rep: over.default_rep.or(self.default_rep),
+            rpc_timeout_secs: over.rpc_timeout_secs.or(self.rpc_timeout_secs),
+            work_timeout_secs: over.work_timeout_secs.or(self.work_timeout_secs),
+            ws_timeout_secs: over.ws_timeout_secs.or(self.ws_timeout_secs),
+            endpoints: over.endpoints.or(self.endpoints),
+        }
+    }
+}
+
+impl ManagerConfig {
+    pub fn new(network: Network) -> ManagerConfig {
+        ManagerConfig {
+            network,
+            rpc_url: String::from(network.default_rpc_url()),
+            fallback_rpc_urls: vec![],
+            quorum: None,
+            ws_url: String::from(network.default_ws_url()),
+            work: WorkSource::Node,
+            work_url: None,
+            default_rep: String::from(account::DEFAULT_REP),
+            rpc_timeout_secs: DEFAULT_RPC_TIMEOUT_SECS,
+            work_timeout_secs: DEFAULT_WORK_TIMEOUT_SECS,
+            ws_timeout_secs: ws::DEFAULT_STALE_AFTER.as_secs(),
+            endpoints: HashMap::new(),
+        }
+    }
+
+    // reads the config file (CONFIG_FILE_PATH if none is given and it exists) and the
+    // environment, then applies `flags` on top.
+    pub fn load(
+        path: Option<&str>,
+        flags: ConfigOverrides,
+    ) -> Result<ManagerConfig, Box<dyn Error>> {
+        let file = match path {
+            Some(p) => ConfigOverrides::from_file(p)?,
+            None if fs::metadata(CONFIG_FILE_PATH).is_ok() => {
+                ConfigOverrides::from_file(CONFIG_FILE_PATH)?
+            }
+            None => ConfigOverrides::default(),
+        };
+        let layers = file.merge(ConfigOverrides::from_env()?).merge(flags);
+        ManagerConfig::from_overrides(layers)
+    }
+
+    pub fn from_overrides(o: ConfigOverrides) -> Result<ManagerConfig, Box<dyn Error>> {
+        let d = ManagerConfig::new(o.network.unwrap_or_default());
+        let c = ManagerConfig {
+            network: d.network,
+            rpc_url: o.rpc_url.unwrap_or(d.rpc_url),
+            fallback_rpc_urls: o.fallback_rpc_urls.unwrap_or(d.fallback_rpc_urls),
+            quorum: o.quorum.or(d.quorum),
+            ws_url: o.ws_url.unwrap_or(d.ws_url),
+            work: o.work.unwrap_or(d.work),
+            work_url: o.work_url.or(d.work_url),
+            default_rep: o.default_rep.unwrap_or(d.default_rep),
+            rpc_timeout_secs: o.rpc_timeout_secs.unwrap_or(d.rpc_timeout_secs),
+            work_timeout_secs: o.work_timeout_secs.unwrap_or(d.work_timeout_secs),
+            ws_timeout_secs: o.ws_timeout_secs.unwrap_or(d.ws_timeout_secs),
+            endpoints: o.endpoints.unwrap_or(d.endpoints),
+        };
+        if !account::valid_addr(&c.default_rep) {
+            return Err("default_rep is not a valid nano address".into());
+        }
+        if let Some(q) = c.quorum {
+            let n = c.rpc_urls().len();
+            if q == 0 || q > n {
+                return Err(format!("quorum must be between 1 and the {} rpc urls", n).into());
+            }
+        }
+        Ok(c)
+    }
+
+    // rpc_url first, then the fallbacks
+    pub fn rpc_urls(&self) -> Vec<&str> {
+        std::iter::once(self.rpc_url.as_str())
+            .chain(self.fallback_rpc_urls.iter().map(String::as_str))
+            .collect()
+    }
+
+    pub fn endpoint_options(&self, url: &str) -> EndpointOptions {
+        self.endpoints.get(url).cloned().unwrap_or_default()
+    }
+
+    pub fn rpc_timeout(&self) -> Duration {
+        Duration::from_secs(self.rpc_timeout_secs)
+    }
+
+    pub fn work_timeout(&self) -> Duration {
+        Duration::from_secs(self.work_timeout_secs)
+    }
+
+    pub fn ws_options(&self) -> ws::WSOptions {
+        ws::WSOptions {
+            stale_after: Duration::from_secs(self.ws_timeout_secs),
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for ManagerConfig {
+    fn default() -> Self {
+        ManagerConfig::new(Network::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn network_picks_default_nodes() {
+        let o: ConfigOverrides = toml::from_str(r#"network = "dev""#).unwrap();
+        let c = ManagerConfig::from_overrides(o).unwrap();
+        assert_eq!(c.rpc_url, Network::Dev.default_rpc_url());
+        assert_eq!(c.ws_url, Network::Dev.default_ws_url());
+        assert_eq!(c.ws_options(), ws::WSOptions::default());
+    }
+
+    #[test]
+    fn later_layers_win() {
+        let file: ConfigOverrides = toml::from_str(
+            r#"
+            rpc_url = "http://file:7076"
+            work = "local"
+            rpc_timeout_secs = 5
+            "#,
+        )
+        .unwrap();
+        let flags = ConfigOverrides {
+            rpc_url: Some(String::from("http://flag:7076")),
+            ..Default::default()
+        };
+        let c = ManagerConfig::from_overrides(file.merge(flags)).unwrap();
+        assert_eq!(c.rpc_url, "http://flag:7076");
+        assert_eq!(c.work, WorkSource::Local);
+        assert_eq!(c.rpc_timeout_secs, 5);
+        assert_eq!(c.ws_url, Network::Live.default_ws_url());
+    }
+
+    #[test]
+    fn quorum_needs_enough_nodes() {
+        let o: ConfigOverrides = toml::from_str(
+            r#"
+            fallback_rpc_urls = ["http://b:7076", "http://c:7076"]
+            quorum = 4
+            "#,
+        )
+        .unwrap();
+        assert!(ManagerConfig::from_overrides(o.clone()).is_err());
+        let ok = ConfigOverrides {
+            quorum: Some(2),
+            ..Default::default()
+        };
+        let c = ManagerConfig::from_overrides(o.merge(ok)).unwrap();
+        assert_eq!(c.rpc_urls().len(), 3);
+    }
+
+    #[test]
+    fn reads_endpoint_options() {
+        let o: ConfigOverrides = toml::from_str(
+            r#"
+            rpc_url = "https://rpc.example"
+
+            [endpoints."https://rpc.example"]
+            bearer_token = "secret"
+            timeout_secs = 5
+            proxy = "socks5://127.0.0.1:9050"
+            headers = { "X-Api-Key" = "key" }
+            "#,
+        )
+        .unwrap();
+        let c = ManagerConfig::from_overrides(o).unwrap();
+        let e = c.endpoint_options(&c.rpc_url);
+        assert_eq!(e.bearer_token.as_deref(), Some("secret"));
+        assert_eq!(e.headers["X-Api-Key"], "key");
+        assert_eq!(e.timeout_secs, Some(5));
+        assert_eq!(
+            c.endpoint_options("http://other"),
+            EndpointOptions::default()
+        );
+        // secrets stay out of printed configs
+        let printed = toml::to_string(&c).unwrap();
+        assert!(!printed.contains("secret") && !printed.contains("key"));
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        assert!(toml::from_str::<ConfigOverrides>(r#"rpc = "http://x""#).is_err());
+    }
+}
@@ -0,0 +1,707 @@
+// a simulated dev network ledger. blocks are checked the way a node's ledger processor
+// checks them (hash, signature, previous, balance, work and receivable), confirmed
+// straight away, and the rpc actions nanors uses are answered from it.
+// serve it over rpc and websocket with mock_node::MockNode::serve_ledger.
+// https://docs.nano.org/integration-guides/the-basics/#blocks-specifications
+// https://docs.nano.org/protocol-design/blocks/
+use crate::account::{self, Account};
+use crate::block::{NanoBlock, SubType, BLOCK_HASH_SIZE, SIG_PREAMBLE};
+use crate::encoding;
+use crate::network::{self, Network};
+use crate::raw::Raw;
+use crate::rpc::NodeError;
+use crate::work;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::error::Error;
+
+// the rpc actions Ledger::handle answers
+pub const ACTIONS: &[&str] = &[
+    "process",
+    "account_info",
+    "account_representative",
+    "accounts_balances",
+    "accounts_frontiers",
+    "accounts_pending",
+    "accounts_receivable",
+    "pending",
+    "receivable",
+    "block_info",
+    "blocks_info",
+    "block_count",
+];
+
+type Confirmed = Box<dyn Fn(Value) + Send>;
+
+pub struct Ledger {
+    network: Network,
+    genesis: Account,
+    blocks: HashMap<String, LedgerBlock>,
+    accounts: HashMap<String, AccountState>,
+    // unreceived sends by hash
+    receivable: HashMap<String, Receivable>,
+    on_confirmation: Option<Confirmed>,
+}
+
+struct LedgerBlock {
+    // as the node shows it: hex link, link_as_account and the subtype filled in
+    contents: NanoBlock,
+    subtype: SubType,
+    amount: u128,
+    height: u64,
+}
+
+struct AccountState {
+    frontier: String,
+    open_block: String,
+    representative_block: String,
+    representative: String,
+    balance: u128,
+    block_count: u64,
+}
+
+struct Receivable {
+    account: String,
+    amount: u128,
+    source: String,
+}
+
+// what checking a block found out about it
+struct Checked {
+    hash: String,
+    link: [u8; 32],
+    subtype: SubType,
+    amount: u128,
+    height: u64,
+}
+
+impl Ledger {
+    // every raw starts out on the dev genesis account, whose key is public
+    pub fn new() -> Result<Ledger, Box<dyn Error>> {
+        let network = Network::Dev;
+        let key: [u8; 32] = hex::decode(network::DEV_GENESIS_KEY)?
+            .as_slice()
+            .try_into()?;
+        let mut genesis = Account::from_key(0, &key, network)?;
+        let supply = Raw::new(u128::MAX);
+        let link = hex::encode_upper(genesis.pk);
+        let mut open = NanoBlock::new(
+            &genesis.addr,
+            &[0u8; 32],
+            &genesis.addr,
+            supply,
+            &link,
+            SubType::Open,
+            "0".repeat(16),
+        )?;
+        open.link_as_account = Some(genesis.addr.clone());
+        let hash = open.hash.clone().ok_or("genesis block without hash")?;
+        genesis.load(supply, hash.clone(), genesis.addr.clone());
+        let mut ledger = Ledger {
+            network,
+            genesis,
+            blocks: HashMap::new(),
+            accounts: HashMap::new(),
+            receivable: HashMap::new(),
+            on_confirmation: None,
+        };
+        ledger.accounts.insert(
+            ledger.genesis.addr.clone(),
+            AccountState {
+                frontier: hash.clone(),
+                open_block: hash.clone(),
+                representative_block: hash.clone(),
+                representative: ledger.genesis.addr.clone(),
+                balance: supply.raw,
+                block_count: 1,
+            },
+        );
+        ledger.blocks.insert(
+            hash,
+            LedgerBlock {
+                contents: open,
+                subtype: SubType::Open,
+                amount: supply.raw,
+                height: 1,
+            },
+        );
+        Ok(ledger)
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    // called with the websocket confirmation message of every block accepted
+    pub fn on_confirmation<F: Fn(Value) + Send + 'static>(&mut self, f: F) {
+        self.on_confirmation = Some(Box::new(f));
+    }
+
+    // sends `amount` from genesis, leaving it receivable for `to`. returns the send hash.
+    pub fn fund(&mut self, to: &str, amount: Raw) -> Result<String, Box<dyn Error>> {
+        let difficulty = self.network.send_difficulty();
+        let threshold: [u8; 8] = hex::decode(difficulty)?.as_slice().try_into()?;
+        let w = work::pow_local(self.genesis.frontier, &threshold)?;
+        self.genesis.cache_work(hex::encode(w));
+        let block = self.genesis.send(amount, to)?;
+        let hash = self.process(&block)?;
+        self.genesis.accept_block(&block)?;
+        Ok(hash)
+    }
+
+    pub fn balance(&self, addr: &str) -> Raw {
+        Raw::new(self.accounts.get(addr).map_or(0, |a| a.balance))
+    }
+
+    pub fn frontier(&self, addr: &str) -> Option<&str> {
+        self.accounts.get(addr).map(|a| a.frontier.as_str())
+    }
+
+    pub fn representative(&self, addr: &str) -> Option<&str> {
+        self.accounts.get(addr).map(|a| a.representative.as_str())
+    }
+
+    // unreceived send hashes for `addr`
+    pub fn receivable(&self, addr: &str) -> Vec<String> {
+        self.receivable
+            .iter()
+            .filter(|(_, r)| r.account == addr)
+            .map(|(hash, _)| hash.clone())
+            .collect()
+    }
+
+    // checks `block` against the ledger and confirms it
+    pub fn process(&mut self, block: &NanoBlock) -> Result<String, NodeError> {
+        let checked = self.check(block)?;
+        let hash = checked.hash.clone();
+        self.apply(block, checked)
+            .map_err(|_| NodeError::InvalidBlock)?;
+        Ok(hash)
+    }
+
+    // the hash is worked out from the fields, whatever the block claims
+    fn check(&self, block: &NanoBlock) -> Result<Checked, NodeError> {
+        let invalid = |_| NodeError::InvalidBlock;
+        if block.kind != "state" {
+            return Err(NodeError::InvalidBlock);
+        }
+        let account_pk = account::decode_addr(&block.account).map_err(invalid)?;
+        let previous = decode_hash(&block.previous)?;
+        let rep_pk = account::decode_addr(&block.representative).map_err(invalid)?;
+        let balance: u128 = block.balance.parse().map_err(|_| NodeError::InvalidBlock)?;
+        let link = decode_link(&block.link)?;
+        let hash = block_hash(&account_pk, &previous, &rep_pk, balance, &link).map_err(invalid)?;
+        let hash = hex::encode_upper(hash);
+        if self.blocks.contains_key(&hash) {
+            return Err(NodeError::Old);
+        }
+        let opening = previous == [0u8; 32];
+        let previous = hex::encode_upper(previous);
+        if !opening && !self.blocks.contains_key(&previous) {
+            return Err(NodeError::GapPrevious);
+        }
+        let signature = block.signature.as_deref().unwrap_or_default();
+        if !account::verify_signature(&block.account, &hash, signature).unwrap_or(false) {
+            return Err(NodeError::BadSignature);
+        }
+        let (prev_balance, height) = match (self.accounts.get(&block.account), opening) {
+            (None, true) => (0, 1),
+            (Some(a), false) if a.frontier == previous => (a.balance, a.block_count + 1),
+            _ => return Err(NodeError::Fork),
+        };
+        let link_hex = hex::encode_upper(link);
+        let (subtype, amount) = if balance < prev_balance {
+            (SubType::Send, prev_balance - balance)
+        } else if balance > prev_balance {
+            let r = match self.receivable.get(&link_hex) {
+                Some(r) => r,
+                None if self.blocks.contains_key(&link_hex) => return Err(NodeError::Unreceivable),
+                None => return Err(NodeError::GapSource),
+            };
+            if r.account != block.account {
+                return Err(NodeError::Unreceivable);
+            }
+            if r.amount != balance - prev_balance {
+                return Err(NodeError::BalanceMismatch);
+            }
+            let subtype = if opening {
+                SubType::Open
+            } else {
+                SubType::Receive
+            };
+            (subtype, r.amount)
+        } else if link == [0u8; 32] && !opening {
+            (SubType::Change, 0)
+        } else {
+            return Err(NodeError::BalanceMismatch);
+        };
+        let root = if opening {
+            account_pk
+        } else {
+            decode_hash(&previous)?
+        };
+        let difficulty = self.network.difficulty_for(subtype);
+        if !work::meets_difficulty(&root, &block.work, difficulty).unwrap_or(false) {
+            return Err(NodeError::InsufficientWork);
+        }
+        Ok(Checked {
+            hash,
+            link,
+            subtype,
+            amount,
+            height,
+        })
+    }
+
+    fn apply(&mut self, block: &NanoBlock, checked: Checked) -> Result<(), Box<dyn Error>> {
+        let Checked {
+            hash,
+            link,
+            subtype,
+            amount,
+            height,
+        } = checked;
+        let link_hex = hex::encode_upper(link);
+        let link_as_account = account::encode_addr(&link)?;
+        let contents = NanoBlock {
+            previous: block.previous.to_uppercase(),
+            link: link_hex.clone(),
+            link_as_account: Some(link_as_account.clone()),
+            hash: Some(hash.clone()),
+            subtype: Some(subtype),
+            ..block.clone()
+        };
+        let balance: u128 = block.balance.parse()?;
+        match subtype {
+            SubType::Send => {
+                let r = Receivable {
+                    account: link_as_account,
+                    amount,
+                    source: block.account.clone(),
+                };
+                self.receivable.insert(hash.clone(), r);
+            }
+            SubType::Receive | SubType::Open => {
+                self.receivable.remove(&link_hex);
+            }
+            _ => {}
+        }
+        let state = self
+            .accounts
+            .entry(block.account.clone())
+            .or_insert_with(|| AccountState {
+                frontier: String::new(),
+                open_block: hash.clone(),
+                representative_block: hash.clone(),
+                representative: block.representative.clone(),
+                balance: 0,
+                block_count: 0,
+            });
+        if state.representative != block.representative {
+            state.representative = block.representative.clone();
+            state.representative_block = hash.clone();
+        }
+        state.frontier = hash.clone();
+        state.balance = balance;
+        state.block_count = height;
+        let confirmation = json!({
+            "account": block.account,
+            "amount": amount.to_string(),
+            "hash": hash,
+            "confirmation_type": "active_quorum",
+            "block": contents,
+        });
+        self.blocks.insert(
+            hash,
+            LedgerBlock {
+                contents,
+                subtype,
+                amount,
+                height,
+            },
+        );
+        if let Some(f) = &self.on_confirmation {
+            f(confirmation);
+        }
+        Ok(())
+    }
+
+    // answers an rpc request like a node would, errors included
+    pub fn handle(&mut self, req: &Value) -> Value {
+        let resp = match req["action"].as_str().unwrap_or_default() {
+            "process" => self.rpc_process(req),
+            "account_info" => self.rpc_account_info(req),
+            "account_representative" => self.rpc_account_representative(req),
+            "accounts_balances" => Ok(self.rpc_accounts_balances(req)),
+            "accounts_frontiers" => Ok(self.rpc_accounts_frontiers(req)),
+            "accounts_pending" | "accounts_receivable" => Ok(self.rpc_accounts_receivable(req)),
+            "pending" | "receivable" => Ok(self.rpc_receivable(req)),
+            "block_info" => self.rpc_block_info(req),
+            "blocks_info" => self.rpc_blocks_info(req),
+            "block_count" => Ok(self.rpc_block_count()),
+            _ => Err(NodeError::UnknownCommand),
+        };
+        resp.unwrap_or_else(|e| json!({ "error": e.to_string() }))
+    }
+
+    fn rpc_process(&mut self, req: &Value) -> Result<Value, NodeError> {
+        // json_block sends an object, otherwise the block is a json string
+        let block = match &req["block"] {
+            Value::String(s) => serde_json::from_str(s),
+            b => serde_json::from_value(b.clone()),
+        };
+        let block: NanoBlock = block.map_err(|_| NodeError::InvalidBlock)?;
+        let checked = self.check(&block)?;
+        if let Some(subtype) = req["subtype"].as_str() {
+            if subtype != subtype_name(checked.subtype) {
+                return Err(NodeError::Other(String::from("Invalid block subtype")));
+            }
+        }
+        let hash = checked.hash.clone();
+        self.apply(&block, checked)
+            .map_err(|_| NodeError::InvalidBlock)?;
+        Ok(json!({ "hash": hash }))
+    }
+
+    fn rpc_account_info(&self, req: &Value) -> Result<Value, NodeError> {
+        let addr = req["account"].as_str().unwrap_or_default();
+        let a = self.accounts.get(addr).ok_or(NodeError::AccountNotFound)?;
+        let mut resp = json!({
+            "frontier": a.frontier,
+            "open_block": a.open_block,
+            "representative_block": a.representative_block,
+            "balance": a.balance.to_string(),
+            "modified_timestamp": "0",
+            "block_count": a.block_count.to_string(),
+            "account_version": "2",
+            "confirmation_height": a.block_count.to_string(),
+            "confirmation_height_frontier": a.frontier,
+        });
+        if flag(&req["representative"]) {
+            resp["representative"] = json!(a.representative);
+        }
+        Ok(resp)
+    }
+
+    fn rpc_account_representative(&self, req: &Value) -> Result<Value, NodeError> {
+        let addr = req["account"].as_str().unwrap_or_default();
+        let rep = self
+            .representative(addr)
+            .ok_or(NodeError::AccountNotFound)?;
+        Ok(json!({ "representative": rep }))
+    }
+
+    fn rpc_accounts_balances(&self, req: &Value) -> Value {
+        let balances: serde_json::Map<String, Value> = accounts(req)
+            .map(|a| {
+                let receivable = self.receivable_sum(a).to_string();
+                let balance = json!({
+                    "balance": self.balance(a).to_string(),
+                    "pending": receivable,
+                    "receivable": receivable,
+                });
+                (String::from(a), balance)
+            })
+            .collect();
+        json!({ "balances": balances })
+    }
+
+    // unopened accounts are left out
+    fn rpc_accounts_frontiers(&self, req: &Value) -> Value {
+        let frontiers: serde_json::Map<String, Value> = accounts(req)
+            .filter_map(|a| self.frontier(a).map(|f| (String::from(a), json!(f))))
+            .collect();
+        json!({ "frontiers": frontiers })
+    }
+
+    fn rpc_accounts_receivable(&self, req: &Value) -> Value {
+        let blocks: serde_json::Map<String, Value> = accounts(req)
+            .map(|a| (String::from(a), self.receivable_blocks(a, req)))
+            .collect();
+        json!({ "blocks": blocks })
+    }
+
+    fn rpc_receivable(&self, req: &Value) -> Value {
+        let addr = req["account"].as_str().unwrap_or_default();
+        json!({ "blocks": self.receivable_blocks(addr, req) })
+    }
+
+    fn rpc_block_info(&self, req: &Value) -> Result<Value, NodeError> {
+        let hash = req["hash"].as_str().unwrap_or_default();
+        self.block_info(hash)
+    }
+
+    fn rpc_blocks_info(&self, req: &Value) -> Result<Value, NodeError> {
+        let mut blocks = serde_json::Map::new();
+        for hash in req["hashes"].as_array().into_iter().flatten() {
+            let hash = hash.as_str().unwrap_or_default();
+            blocks.insert(String::from(hash), self.block_info(hash)?);
+        }
+        Ok(json!({ "blocks": blocks }))
+    }
+
+    fn rpc_block_count(&self) -> Value {
+        let count = self.blocks.len().to_string();
+        json!({ "count": count, "unchecked": "0", "cemented": count })
+    }
+
+    fn block_info(&self, hash: &str) -> Result<Value, NodeError> {
+        let b = self
+            .blocks
+            .get(&hash.to_uppercase())
+            .ok_or(NodeError::BlockNotFound)?;
+        Ok(json!({
+            "block_account": b.contents.account,
+            "amount": b.amount.to_string(),
+            "balance": b.contents.balance,
+            "height": b.height.to_string(),
+            "local_timestamp": "0",
+            "confirmed": "true",
+            "subtype": subtype_name(b.subtype),
+            "contents": b.contents,
+        }))
+    }
+
+    fn receivable_sum(&self, addr: &str) -> u128 {
+        self.receivable
+            .values()
+            .filter(|r| r.account == addr)
+            .map(|r| r.amount)
+            .sum()
+    }
+
+    // in the shape the request asks for: hashes, amounts by hash, or amounts and sources
+    fn receivable_blocks(&self, addr: &str, req: &Value) -> Value {
+        let threshold: u128 = req["threshold"]
+            .as_str()
+            .and_then(|t| t.parse().ok())
+            .unwrap_or(0);
+        let count = req["count"]
+            .as_str()
+            .and_then(|c| c.parse().ok())
+            .unwrap_or(usize::MAX);
+        let mut blocks: Vec<(&String, &Receivable)> = self
+            .receivable
+            .iter()
+            .filter(|(_, r)| r.account == addr && r.amount >= threshold)
+            .collect();
+        blocks.sort_by(|a, b| b.1.amount.cmp(&a.1.amount).then(a.0.cmp(b.0)));
+        blocks.truncate(count);
+        if blocks.is_empty() {
+            return json!("");
+        }
+        if flag(&req["source"]) {
+            let m: serde_json::Map<String, Value> = blocks
+                .into_iter()
+                .map(|(h, r)| {
+                    let v = json!({ "amount": r.amount.to_string(), "source": r.source });
+                    (h.clone(), v)
+                })
+                .collect();
+            json!(m)
+        } else if req.get("threshold").is_some() {
+            let m: serde_json::Map<String, Value> = blocks
+                .into_iter()
+                .map(|(h, r)| (h.clone(), json!(r.amount.to_string())))
+                .collect();
+            json!(m)
+        } else {
+            json!(blocks.into_iter().map(|(h, _)| h).collect::<Vec<_>>())
+        }
+    }
+}
+
+fn subtype_name(subtype: SubType) -> &'static str {
+    match subtype {
+        SubType::Send => "send",
+        SubType::Receive => "receive",
+        SubType::Open => "open",
+        SubType::Change => "change",
+        SubType::Epoch => "epoch",
+    }
+}
+
+// https://docs.nano.org/integration-guides/the-basics/#self-signed-blocks
+fn block_hash(
+    account: &[u8; 32],
+    previous: &[u8; 32],
+    representative: &[u8; 32],
+    balance: u128,
+    link: &[u8; 32],
+) -> Result<[u8; BLOCK_HASH_SIZE], Box<dyn Error>> {
+    let mut preamble = [0u8; 32];
+    preamble[31] = SIG_PREAMBLE;
+    let data = [
+        &preamble[..],
+        account,
+        previous,
+        representative,
+        &balance.to_be_bytes(),
+        link,
+    ]
+    .concat();
+    Ok((*encoding::blake2bv(BLOCK_HASH_SIZE, &data)?).try_into()?)
+}
+
+fn decode_hash(hash: &str) -> Result<[u8; 32], NodeError> {
+    hex::decode(hash)
+        .ok()
+        .and_then(|h| h.as_slice().try_into().ok())
+        .ok_or(NodeError::InvalidBlock)
+}
+
+// the node takes a link as hex or as an account
+fn decode_link(link: &str) -> Result<[u8; 32], NodeError> {
+    if account::valid_addr(link) {
+        account::decode_addr(link).map_err(|_| NodeError::InvalidBlock)
+    } else {
+        decode_hash(link)
+    }
+}
+
+fn accounts(req: &Value) -> impl Iterator<Item = &str> {
+    req["accounts"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|a| a.as_str())
+}
+
+// rpc booleans come as true or "true"
+fn flag(v: &Value) -> bool {
+    v.as_bool() == Some(true) || v.as_str() == Some("true")
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const TEST_SEED: [u8; 32] = [
+        137, 197, 104, 229, 75, 120, 185, 178, 9, 190, 248, 22, 140, 246, 140, 143, 247, 174, 97,
+        154, 204, 80, 167, 39, 121, 67, 35, 190, 48, 60, 244, 11,
+    ];
+
+    fn with_work(a: &mut Account, subtype: SubType) {
+        let root = if a.frontier == [0u8; 32] {
+            a.pk
+        } else {
+            a.frontier
+        };
+        let difficulty = Network::Dev.difficulty_for(subtype);
+        let threshold: [u8; 8] = hex::decode(difficulty)
+            .unwrap()
+            .as_slice()
+            .try_into()
+            .unwrap();
+        a.cache_work(hex::encode(work::pow_local(root, &threshold).unwrap()));
+    }
+
+    // a funded and opened account
+    fn opened(ledger: &mut Ledger, index: u32, amount: u128) -> Account {
+        let mut a = Account::new(index, &TEST_SEED, Network::Dev).unwrap();
+        let send = ledger.fund(&a.addr, Raw::new(amount)).unwrap();
+        with_work(&mut a, SubType::Open);
+        let open = a.open(Raw::new(amount), &send).unwrap();
+        assert_eq!(ledger.process(&open).unwrap(), open.hash.clone().unwrap());
+        a.accept_block(&open).unwrap();
+        a
+    }
+
+    #[test]
+    fn genesis_key_matches_dev_genesis() {
+        let ledger = Ledger::new().unwrap();
+        assert_eq!(ledger.genesis.addr, Network::Dev.genesis_account());
+        assert_eq!(ledger.balance(&ledger.genesis.addr), Raw::new(u128::MAX));
+    }
+
+    #[test]
+    fn sends_and_receives() {
+        let mut ledger = Ledger::new().unwrap();
+        let mut a = opened(&mut ledger, 0, 100);
+        let b = Account::new(1, &TEST_SEED, Network::Dev).unwrap();
+        with_work(&mut a, SubType::Send);
+        let send = a.send(Raw::new(40), &b.addr).unwrap();
+        let hash = ledger.process(&send).unwrap();
+        a.accept_block(&send).unwrap();
+        assert_eq!(ledger.balance(&a.addr), Raw::new(60));
+        assert_eq!(ledger.receivable(&b.addr), vec![hash.clone()]);
+        let info = ledger.handle(&json!({ "action": "block_info", "hash": hash }));
+        assert_eq!(info["subtype"], "send");
+        assert_eq!(info["contents"]["link_as_account"], b.addr);
+
+        with_work(&mut a, SubType::Change);
+        let change = a.change(&b.addr).unwrap();
+        ledger.process(&change).unwrap();
+        assert_eq!(ledger.representative(&a.addr), Some(b.addr.as_str()));
+    }
+
+    #[test]
+    fn rejects_invalid_blocks() {
+        let mut ledger = Ledger::new().unwrap();
+        let mut a = opened(&mut ledger, 0, 100);
+        let b = Account::new(1, &TEST_SEED, Network::Dev).unwrap();
+        with_work(&mut a, SubType::Send);
+        let send = a.send(Raw::new(1), &b.addr).unwrap();
+
+        let mut forged = send.clone();
+        forged.balance = String::from("0");
+        assert_eq!(ledger.process(&forged), Err(NodeError::BadSignature));
+        let mut lazy = send.clone();
+        lazy.work = "0".repeat(16);
+        // the signature does not cover work
+        assert_eq!(ledger.process(&lazy), Err(NodeError::InsufficientWork));
+
+        ledger.process(&send).unwrap();
+        assert_eq!(ledger.process(&send), Err(NodeError::Old));
+        // a second block on the same previous
+        let other = a.send(Raw::new(2), &b.addr).unwrap();
+        assert_eq!(ledger.process(&other), Err(NodeError::Fork));
+        a.accept_block(&send).unwrap();
+        a.frontier = [9u8; 32];
+        // gaps are found before work is checked
+        a.cache_work("0".repeat(16));
+        let gap = a.send(Raw::new(1), &b.addr).unwrap();
+        assert_eq!(ledger.process(&gap), Err(NodeError::GapPrevious));
+    }
+
+    #[test]
+    fn receives_only_what_was_sent() {
+        let mut ledger = Ledger::new().unwrap();
+        let mut a = Account::new(0, &TEST_SEED, Network::Dev).unwrap();
+        let send = ledger.fund(&a.addr, Raw::new(50)).unwrap();
+        with_work(&mut a, SubType::Open);
+        let greedy = a.open(Raw::new(51), &send).unwrap();
+        assert_eq!(ledger.process(&greedy), Err(NodeError::BalanceMismatch));
+        let unknown = a.open(Raw::new(50), &"07".repeat(32)).unwrap();
+        assert_eq!(ledger.process(&unknown), Err(NodeError::GapSource));
+
+        let mut b = Account::new(1, &TEST_SEED, Network::Dev).unwrap();
+        with_work(&mut b, SubType::Open);
+        let thief = b.open(Raw::new(50), &send).unwrap();
+        assert_eq!(ledger.process(&thief), Err(NodeError::Unreceivable));
+    }
+
+    #[test]
+    fn answers_receivable_in_every_shape() {
+        let mut ledger = Ledger::new().unwrap();
+        let a = Account::new(0, &TEST_SEED, Network::Dev).unwrap();
+        let hash = ledger.fund(&a.addr, Raw::new(5)).unwrap();
+        let list = ledger.handle(&json!({ "action": "pending", "account": a.addr }));
+        assert_eq!(list["blocks"], json!([hash]));
+        let amounts = ledger.handle(&json!({
+            "action": "receivable", "account": a.addr, "threshold": "1",
+        }));
+        assert_eq!(amounts["blocks"][&hash], "5");
+        let sources = ledger.handle(&json!({
+            "action": "accounts_receivable", "accounts": [a.addr], "source": "true",
+        }));
+        assert_eq!(
+            sources["blocks"][&a.addr][&hash]["source"],
+            Network::Dev.genesis_account()
+        );
+        let info = ledger.handle(&json!({ "action": "account_info", "account": a.addr }));
+        assert_eq!(info["error"], "Account not found");
+    }
+}
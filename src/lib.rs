@@ -1,9 +1,16 @@
 pub mod account;
 pub mod block;
+pub mod config;
 pub mod encoding;
+#[cfg(feature = "test-support")]
+pub mod ledger;
 pub mod manager;
+#[cfg(feature = "test-support")]
+pub mod mock_node;
+pub mod network;
 pub mod rpc;
 pub mod wallet;
 pub mod work;
+pub mod work_server;
 pub mod ws;
 pub mod raw;
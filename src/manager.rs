@@ -1,49 +1,607 @@
 use crate::account;
 use crate::block;
+use crate::config;
+use crate::raw;
 use crate::rpc;
 use crate::wallet;
 use crate::work;
 use crate::ws;
-use crate::raw;
 
-use futures::lock::Mutex;
-use tokio::sync::{mpsc, oneshot};
-use std::sync::Arc;
+use bigdecimal::BigDecimal;
+use futures::stream::{self, FuturesUnordered, StreamExt};
+use std::collections::{HashMap, VecDeque};
 use std::convert::TryInto;
 use std::error::Error;
-use bigdecimal::BigDecimal;
-
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio::time::{self, Duration};
 
-//other good nodes "https://mynano.ninja/api/node";
-const PUBLIC_NANO_RPC_HOST: &str = "https://proxy.nanos.cc/proxy";
-const PUBLIC_NANO_WS_HOST: &str = "wss://ws.mynano.ninja/";
-const WORK_LOCAL: bool = false;
+// accounts per batched rpc call during sync
+const SYNC_BATCH_SIZE: usize = 100;
+// accounts receiving at the same time during sync
+const SYNC_CONCURRENCY: usize = 8;
+// receivable blocks fetched per account per sync
+const SYNC_RECEIVABLE_COUNT: u64 = 100;
+// smallest receivable amount picked up by sync, in raw
+const SYNC_RECEIVE_THRESHOLD: &str = "1";
+// confirmed hashes remembered for waiters that start late
+const RECENT_CONFIRMATIONS: usize = 1000;
+// commands queued for a ManagerHandle before senders wait
+const HANDLE_QUEUE: usize = 32;
+// events kept for a slow subscriber before it lags
+const EVENT_BUFFER: usize = 100;
 
 pub struct Manager {
-    rpc: rpc::ClientRpc,
+    node: Node,
     wallet: Option<wallet::Wallet>,
-    cancel: Option<tokio::sync::oneshot::Sender<()>>,
+    observer: Option<Observer>,
+    // changes the accounts watched by the running confirmation subscription
+    subscription: Option<ws::SubscriptionHandle>,
+    confirmed: Confirmed,
+}
+
+// our blocks as the websocket reports them settled. the recent ones are kept for
+// waiters that start after the confirmation arrived.
+#[derive(Clone)]
+struct Confirmed {
+    tx: broadcast::Sender<Settled>,
+    recent: Arc<std::sync::Mutex<VecDeque<Settled>>>,
+}
+
+#[derive(Debug, Clone)]
+enum Settled {
+    Confirmed(String),
+    // a competing block on the same previous won the election
+    Replaced { ours: String, by: String },
+}
+
+impl Settled {
+    // the outcome for a waiter on `hash`, if this is about it
+    fn outcome(&self, hash: &str) -> Option<Result<(), ConfirmError>> {
+        match self {
+            Settled::Confirmed(h) if h.eq_ignore_ascii_case(hash) => Some(Ok(())),
+            Settled::Replaced { ours, by } if ours.eq_ignore_ascii_case(hash) => {
+                Some(Err(ConfirmError::Replaced {
+                    hash: ours.clone(),
+                    by: by.clone(),
+                }))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Confirmed {
+    fn new() -> Confirmed {
+        Confirmed {
+            tx: broadcast::channel(100).0,
+            recent: Arc::new(std::sync::Mutex::new(VecDeque::new())),
+        }
+    }
+
+    fn add(&self, settled: Settled) {
+        let mut recent = self.recent.lock().unwrap();
+        if recent.len() == RECENT_CONFIRMATIONS {
+            recent.pop_front();
+        }
+        recent.push_back(settled.clone());
+        drop(recent);
+        // nobody waiting is fine
+        let _ = self.tx.send(settled);
+    }
+
+    // how `hash` settled if it did already, and what settles from now on
+    fn watch(
+        &self,
+        hash: &str,
+    ) -> (
+        Option<Result<(), ConfirmError>>,
+        broadcast::Receiver<Settled>,
+    ) {
+        let recent = self.recent.lock().unwrap();
+        let rx = self.tx.subscribe();
+        (recent.iter().find_map(|s| s.outcome(hash)), rx)
+    }
+}
+
+// how long to wait for a block to be cemented, see Manager::confirmation
+#[derive(Debug, Clone, Copy)]
+pub struct ConfirmOptions {
+    pub timeout: Duration,
+    // block_info is checked this often, in case the websocket missed it
+    pub poll_interval: Duration,
+    // ask the node to start an election (block_confirm) when still unconfirmed by then
+    pub block_confirm_after: Duration,
+}
+
+impl Default for ConfirmOptions {
+    fn default() -> Self {
+        ConfirmOptions {
+            timeout: Duration::from_secs(120),
+            poll_interval: Duration::from_secs(5),
+            block_confirm_after: Duration::from_secs(15),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfirmError {
+    Timeout { hash: String, after: Duration },
+    // a competing block on the same previous was confirmed instead
+    Replaced { hash: String, by: String },
+    // the waiting task panicked or the runtime is shutting down
+    Aborted(String),
+}
+
+impl fmt::Display for ConfirmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfirmError::Timeout { hash, after } => {
+                write!(f, "block {} not confirmed within {:?}", hash, after)
+            }
+            ConfirmError::Replaced { hash, by } => {
+                write!(f, "block {} lost to the competing block {}", hash, by)
+            }
+            ConfirmError::Aborted(e) => write!(f, "waiting for confirmation failed: {}", e),
+        }
+    }
+}
+
+impl Error for ConfirmError {}
+
+// what the manager did or noticed, from Manager::events. amounts are in raw.
+#[derive(Debug, Clone)]
+pub enum Event {
+    // a send to one of our accounts, about to be received
+    IncomingPending {
+        account: String,
+        send: String,
+        amount: raw::Raw,
+    },
+    Received {
+        account: String,
+        hash: String,
+        send: String,
+        amount: raw::Raw,
+    },
+    Sent {
+        account: String,
+        hash: String,
+        to: String,
+        amount: raw::Raw,
+    },
+    // a block on one of our chains was cemented
+    Confirmed {
+        account: String,
+        hash: String,
+    },
+    RepresentativeChanged {
+        account: String,
+        hash: String,
+        rep: String,
+    },
+    SyncStarted,
+    // also after a failed sync
+    SyncFinished,
+    // the websocket dropped. confirmations are missed until it is restored.
+    ConnectionLost(String),
+    // the websocket is back, a sync follows
+    ConnectionRestored,
+    // something that went wrong in the background
+    Error(String),
+    // something odd in the background that did not stop anything, eg. a node
+    // disagreeing with the others or a connection attempt that is retried
+    Warning(String),
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::IncomingPending {
+                account, amount, ..
+            } => write!(f, "{} nano incoming to {}", amount.to_mnano(), account),
+            Event::Received {
+                account, amount, ..
+            } => write!(f, "{} received {} nano", account, amount.to_mnano()),
+            Event::Sent {
+                account,
+                to,
+                amount,
+                ..
+            } => write!(f, "{} sent {} nano to {}", account, amount.to_mnano(), to),
+            Event::Confirmed { hash, .. } => write!(f, "block {} confirmed", hash),
+            Event::RepresentativeChanged { account, rep, .. } => {
+                write!(f, "{} changed representative to {}", account, rep)
+            }
+            Event::SyncStarted => write!(f, "synchronizing accounts"),
+            Event::SyncFinished => write!(f, "accounts synchronized"),
+            Event::ConnectionLost(why) => write!(f, "websocket connection lost: {}", why),
+            Event::ConnectionRestored => write!(f, "websocket connection restored"),
+            Event::Error(e) => write!(f, "{}", e),
+            Event::Warning(w) => write!(f, "warning: {}", w),
+        }
+    }
+}
+
+// the task subscribed to confirmations for the wallet's accounts
+struct Observer {
+    cancel: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+// resolves once a block is cemented. dropping it stops waiting.
+pub struct Confirmation {
+    task: JoinHandle<Result<(), ConfirmError>>,
+}
+
+impl Future for Confirmation {
+    type Output = Result<(), ConfirmError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match Pin::new(&mut self.task).poll(cx) {
+            Poll::Ready(Ok(res)) => Poll::Ready(res),
+            Poll::Ready(Err(e)) => Poll::Ready(Err(ConfirmError::Aborted(e.to_string()))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for Confirmation {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+type Reply<T> = oneshot::Sender<Result<T, String>>;
+
+enum Command {
+    Shared(Request),
+    Exclusive(Exclusive),
+}
+
+// answered with the manager borrowed, so several at once
+enum Request {
+    Send {
+        amount: BigDecimal,
+        from: String,
+        to: String,
+        reply: Reply<String>,
+    },
+    Change {
+        acct: String,
+        rep: String,
+        reply: Reply<String>,
+    },
+    Receive {
+        acct: String,
+        reply: Reply<Vec<String>>,
+    },
+    AccountsInfo {
+        reply: Reply<Vec<account::AccountInfo>>,
+    },
+}
+
+// need the manager to themselves
+enum Exclusive {
+    AccountAdd { pw: String, reply: Reply<()> },
+    Shutdown(oneshot::Sender<()>),
+}
+
+// a Manager running in its own task, from Manager::spawn. clones can be used from
+// any task. the manager shuts down once asked to or once every clone is dropped.
+#[derive(Clone)]
+pub struct ManagerHandle {
+    tx: mpsc::Sender<Command>,
+    events: broadcast::Sender<Event>,
+}
+
+impl ManagerHandle {
+    pub fn events(&self) -> broadcast::Receiver<Event> {
+        self.events.subscribe()
+    }
+
+    pub async fn send(
+        &self,
+        amount: BigDecimal,
+        from: &str,
+        to: &str,
+    ) -> Result<String, Box<dyn Error>> {
+        let (from, to) = (String::from(from), String::from(to));
+        self.request(|reply| {
+            Command::Shared(Request::Send {
+                amount,
+                from,
+                to,
+                reply,
+            })
+        })
+        .await
+    }
+
+    pub async fn change(&self, acct: &str, rep: &str) -> Result<String, Box<dyn Error>> {
+        let (acct, rep) = (String::from(acct), String::from(rep));
+        self.request(|reply| Command::Shared(Request::Change { acct, rep, reply }))
+            .await
+    }
+
+    pub async fn receive(&self, acct: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let acct = String::from(acct);
+        self.request(|reply| Command::Shared(Request::Receive { acct, reply }))
+            .await
+    }
+
+    pub async fn account_add(&self, pw: &str) -> Result<(), Box<dyn Error>> {
+        let pw = String::from(pw);
+        self.request(|reply| Command::Exclusive(Exclusive::AccountAdd { pw, reply }))
+            .await
+    }
+
+    pub async fn get_accounts_info(&self) -> Result<Vec<account::AccountInfo>, Box<dyn Error>> {
+        self.request(|reply| Command::Shared(Request::AccountsInfo { reply }))
+            .await
+    }
+
+    // returns once commands already started are done and the websocket is closed.
+    // commands sent after it fail.
+    pub async fn shutdown(&self) {
+        let (done, stopped) = oneshot::channel();
+        if self
+            .tx
+            .send(Command::Exclusive(Exclusive::Shutdown(done)))
+            .await
+            .is_ok()
+        {
+            let _ = stopped.await;
+        }
+    }
+
+    async fn request<T, F>(&self, command: F) -> Result<T, Box<dyn Error>>
+    where
+        F: FnOnce(Reply<T>) -> Command,
+    {
+        let (reply, rx) = oneshot::channel();
+        if self.tx.send(command(reply)).await.is_err() {
+            return Err("manager has shut down".into());
+        }
+        match rx.await {
+            Ok(res) => Ok(res?),
+            Err(_) => Err("manager has shut down".into()),
+        }
+    }
+}
+
+// the node we talk to and where work comes from. cloned into the confirmation
+// watcher so both use the same settings.
+#[derive(Clone)]
+struct Node {
+    rpc: rpc::ClientRpc,
+    work_rpc: rpc::ClientRpc,
+    config: config::ManagerConfig,
+    // our unconfirmed blocks by account and previous, to spot a competing block
+    // winning the election
+    published: Arc<std::sync::Mutex<HashMap<(String, String), String>>>,
+    events: broadcast::Sender<Event>,
+}
+
+impl Node {
+    fn new(config: config::ManagerConfig) -> Result<Node, Box<dyn Error>> {
+        let rpc = config
+            .rpc_urls()
+            .into_iter()
+            .fold(rpc::ClientRpc::builder(), |b, url| {
+                b.endpoint_with(url, config.endpoint_options(url))
+            })
+            .timeout(config.rpc_timeout())
+            .build()?;
+        // the work provider first, then the rpc nodes
+        let mut work_urls = config.work_url.as_deref().into_iter().collect::<Vec<_>>();
+        for url in config.rpc_urls() {
+            if !work_urls.contains(&url) {
+                work_urls.push(url);
+            }
+        }
+        let work_rpc = work_urls
+            .into_iter()
+            .fold(rpc::ClientRpc::builder(), |b, url| {
+                b.endpoint_with(url, config.endpoint_options(url))
+            })
+            .timeout(config.work_timeout())
+            .build()?;
+        Ok(Node {
+            rpc,
+            work_rpc,
+            config,
+            published: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            events: broadcast::channel(EVENT_BUFFER).0,
+        })
+    }
+
+    // with a quorum configured the answer has to be agreed on by a majority of nodes.
+    // None when the account is not opened.
+    async fn account_info(
+        &self,
+        addr: &str,
+    ) -> Result<Option<rpc::RPCAccountInfoResp>, Box<dyn Error>> {
+        match self.config.quorum {
+            None => self.rpc.account_info(addr).await,
+            Some(q) => {
+                let resp = self.rpc.account_info_quorum(addr, q).await;
+                let resp = resp.map_err(|e| format!("account info for {}: {}", addr, e))?;
+                self.warn_dissent(&resp, addr);
+                match resp.value {
+                    Ok(info) => Ok(Some(info)),
+                    Err(rpc::NodeError::AccountNotFound) => Ok(None),
+                    Err(e) => Err(format!("account info for {}: {}", addr, e).into()),
+                }
+            }
+        }
+    }
+
+    async fn block_info(
+        &self,
+        hash: &str,
+    ) -> Result<Option<rpc::RPCBlockInfoResp>, Box<dyn Error>> {
+        match self.config.quorum {
+            None => self.rpc.block_info(hash).await,
+            Some(q) => {
+                let resp = self.rpc.block_info_quorum(hash, q).await;
+                let resp = resp.map_err(|e| format!("block info for {}: {}", hash, e))?;
+                self.warn_dissent(&resp, hash);
+                Ok(resp.value.ok())
+            }
+        }
+    }
+
+    // publishes a block and moves the account onto it. when the node refuses it the
+    // account is left as it was, with stale work dropped. fork, old and gap errors
+    // are passed on as they are, see Manager::publish.
+    async fn publish(
+        &self,
+        account: &mut account::Account,
+        block: &block::NanoBlock,
+    ) -> Result<String, Box<dyn Error>> {
+        match self.rpc.process(block).await {
+            Ok(resp) => {
+                account.accept_block(block)?;
+                let root = (block.account.clone(), block.previous.to_uppercase());
+                self.published
+                    .lock()
+                    .unwrap()
+                    .insert(root, resp.hash.clone());
+                Ok(resp.hash)
+            }
+            Err(e) => match e.downcast_ref::<rpc::NodeError>() {
+                Some(rpc::NodeError::InsufficientWork) => {
+                    account.clear_work();
+                    Err(format!("{}. work was dropped, please retry", e).into())
+                }
+                _ => Err(e),
+            },
+        }
+    }
+
+    // the block we published after `previous` on `account`, forgotten once asked for
+    fn take_published(&self, account: &str, previous: &str) -> Option<String> {
+        let root = (String::from(account), previous.to_uppercase());
+        self.published.lock().unwrap().remove(&root)
+    }
+
+    // `hash` is cemented and so is everything before it on its chain, which can no
+    // longer lose a fork
+    fn settle_published(&self, hash: &str) {
+        let mut published = self.published.lock().unwrap();
+        let mut hash = hash.to_uppercase();
+        while let Some(root) = published
+            .iter()
+            .find(|(_, h)| h.to_uppercase() == hash)
+            .map(|(root, _)| root.clone())
+        {
+            published.remove(&root);
+            hash = root.1;
+        }
+    }
+
+    // reloads frontier, balance and representative from the node, dropping work
+    // for the old frontier
+    async fn resync(&self, account: &mut account::Account) -> Result<(), Box<dyn Error>> {
+        match self.account_info(&account.addr).await? {
+            Some(info) => account.load(info.balance.parse()?, info.frontier, info.representative),
+            None => account.load(
+                raw::Raw::new(0),
+                hex::encode([0u8; block::BLOCK_HASH_SIZE]),
+                self.config.default_rep.clone(),
+            ),
+        }
+        account.clear_work();
+        Ok(())
+    }
+
+    // nobody listening is fine
+    fn event(&self, event: Event) {
+        let _ = self.events.send(event);
+    }
+
+    fn warn_dissent<T>(&self, resp: &rpc::QuorumResp<T>, about: &str) {
+        if !resp.dissent.is_empty() {
+            self.event(Event::Warning(format!(
+                "{} of {} nodes disagree about {}: {}",
+                resp.dissent.len(),
+                resp.responded,
+                about,
+                resp.dissent.join(", ")
+            )));
+        }
+    }
 }
 
 impl Manager {
-    pub async fn new() -> Result<Manager, Box<dyn std::error::Error>> {
-        let rpc = rpc::ClientRpc::new(PUBLIC_NANO_RPC_HOST)?;
+    pub async fn new(config: config::ManagerConfig) -> Result<Manager, Box<dyn std::error::Error>> {
         Ok(Manager {
-            rpc,
+            node: Node::new(config)?,
             wallet: None,
-            cancel: None,
+            observer: None,
+            subscription: None,
+            confirmed: Confirmed::new(),
         })
     }
 
+    pub fn config(&self) -> &config::ManagerConfig {
+        &self.node.config
+    }
+
+    // errors if no node can be reached
+    pub async fn check_node(&self) -> Result<(), Box<dyn Error>> {
+        self.node.rpc.connect().await?;
+        self.node.rpc.check_health().await;
+        Ok(())
+    }
+
+    pub fn node_health(&self) -> Vec<rpc::EndpointHealth> {
+        self.node.rpc.health()
+    }
+
+    // requests per rpc action since the node was set
+    pub fn node_stats(&self) -> HashMap<String, rpc::ActionStats> {
+        self.node.rpc.stats()
+    }
+
+    // switch rpc and websocket nodes at runtime. the new rpc node has to be reachable.
+    pub async fn set_node(&mut self, rpc_url: &str, ws_url: &str) -> Result<(), Box<dyn Error>> {
+        let mut config = self.node.config.clone();
+        config.rpc_url = String::from(rpc_url);
+        config.ws_url = String::from(ws_url);
+        let mut node = Node::new(config)?;
+        node.rpc.connect().await?;
+        // subscribers keep their stream, and blocks published on the old node are
+        // still watched for forks
+        node.events = self.node.events.clone();
+        node.published = self.node.published.clone();
+        self.node = node;
+        if self.wallet.is_some() {
+            self.stop_observing().await;
+            self.ws_observe_accounts().await?;
+        }
+        Ok(())
+    }
+
     pub fn has_wallet(&self) -> bool {
         self.wallet.is_some()
     }
 
     pub async fn set_wallet(&mut self, wallet: wallet::Wallet) -> Result<(), Box<dyn Error>> {
-        if self.wallet.is_some() {
-            let tx = self.cancel.take();
-            tx.unwrap().send(()).unwrap();
+        if wallet.network != self.node.config.network {
+            return Err(format!(
+                "wallet {} is for the {} network, manager is on {}",
+                wallet.name, wallet.network, self.node.config.network
+            )
+            .into());
         }
+        self.stop_observing().await;
         self.wallet = Some(wallet);
         self.synchronize().await?;
         self.ws_observe_accounts().await?;
@@ -61,28 +619,52 @@ impl Manager {
         if self.wallet.is_none() {
             return vec![];
         }
-        let accounts = self.get_accounts().lock().await;
-        accounts
-            .iter()
-            .map(|a| account::AccountInfo {
+        let mut infos = vec![];
+        for a in wallet::account_locks(self.get_accounts()).await {
+            let a = a.lock().await;
+            infos.push(account::AccountInfo {
                 index: a.index,
                 addr: a.addr.clone(),
                 balance_mnano: a.balance.to_mnano(),
-            })
-            .collect()
+            });
+        }
+        infos
+    }
+
+    // https://docs.nano.org/commands/rpc-protocol/#work_validate
+    pub fn validate_work(
+        &self,
+        root: &str,
+        work: &str,
+        subtype: Option<block::SubType>,
+    ) -> Result<work::WorkValidation, Box<dyn Error>> {
+        let root = hex::decode(root)?.as_slice().try_into()?;
+        work::validate_work(&root, work, subtype, self.node.config.network)
     }
 
     pub async fn account_add(&mut self, pw: &str) -> Result<(), Box<dyn Error>> {
         if self.wallet.is_some() {
             self.wallet.as_mut().unwrap().add_account(pw).await?;
+            let added = self.get_accounts().lock().await.last().cloned();
+            if let Some(a) = added {
+                a.lock().await.rep = self.node.config.default_rep.clone();
+                // the account is added either way, it just is not watched yet
+                if let Some(s) = &self.subscription {
+                    if let Err(e) = s.add_accounts(vec![a.addr.clone()]) {
+                        let w = format!("not watching {} for confirmations: {}", a.addr, e);
+                        self.node.event(Event::Warning(w));
+                    }
+                }
+            }
         } else {
             return Err("no wallet set".into());
         }
         Ok(())
     }
 
+    // waits for anything else going on with the account, other accounts are not held up
     pub async fn send(
-        &mut self,
+        &self,
         amount: BigDecimal,
         from: &str,
         to: &str,
@@ -90,108 +672,390 @@ impl Manager {
         if self.wallet.is_none() {
             return Err("no wallet set".into());
         }
-        let accounts = &mut self.wallet.as_mut().unwrap().accounts.lock().await;
-        let from = match accounts.iter_mut().find(|a| a.addr == from) {
+        let from = match wallet::find_account(self.get_accounts(), from).await {
             Some(a) => a,
             None => return Err("from address not found".into()),
         };
-        if !from.has_work() {
-            Manager::cache_work(
-                from,
-                &self.rpc,
-                from.frontier.clone(),
-                work::DEFAULT_DIFFICULTY,
-            )
-            .await?;
-        }
+        let from = &mut *from.lock().await;
+        let difficulty = from.network.send_difficulty();
         let amount = raw::Raw::from_mnano(amount);
-        let block = from.send(amount, to)?;
-        if let Some(hash) = self.rpc.process(&block).await {
-            // todo: just do this in acct.create_block.
-            // do a rollback somehow..?
-            from.accept_block(&block)?;
-            return Ok(hash.hash);
-        }
-        Err("could not process send block".into())
+        let hash = Manager::publish(&self.node, from, difficulty, |a| a.send(amount, to))
+            .await
+            .map_err(|e| format!("could not process send block: {}", e))?;
+        self.node.event(Event::Sent {
+            account: from.addr.clone(),
+            hash: hash.clone(),
+            to: String::from(to),
+            amount,
+        });
+        Ok(hash)
     }
 
-    pub async fn change(&mut self, acct: &str, rep: &str) -> Result<String, Box<dyn Error>> {
+    pub async fn change(&self, acct: &str, rep: &str) -> Result<String, Box<dyn Error>> {
         if self.wallet.is_none() {
             return Err("no wallet set".into());
         }
-        let accounts = &mut self.wallet.as_mut().unwrap().accounts.lock().await;
-        let for_acct = match accounts.iter_mut().find(|a| a.addr == acct) {
+        let for_acct = match wallet::find_account(self.get_accounts(), acct).await {
             Some(a) => a,
             None => return Err("from address not found".into()),
         };
-        if !for_acct.has_work() {
-            Manager::cache_work(
-                for_acct,
-                &self.rpc,
-                for_acct.frontier.clone(),
-                work::DEFAULT_DIFFICULTY,
-            )
-            .await?;
+        let for_acct = &mut *for_acct.lock().await;
+        let difficulty = for_acct.network.send_difficulty();
+        let hash = Manager::publish(&self.node, for_acct, difficulty, |a| a.change(rep))
+            .await
+            .map_err(|e| format!("could not process change block: {}", e))?;
+        self.node.event(Event::RepresentativeChanged {
+            account: for_acct.addr.clone(),
+            hash: hash.clone(),
+            rep: String::from(rep),
+        });
+        Ok(hash)
+    }
+
+    // receives what is waiting for the account now rather than on its confirmation.
+    // returns the hashes of the receive blocks.
+    pub async fn receive_pending(&self, acct: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        if self.wallet.is_none() {
+            return Err("no wallet set".into());
         }
-        let block = for_acct.change(rep)?;
-        if let Some(hash) = self.rpc.process(&block).await {
-            // todo: just do this in acct.create_block.
-            // do a rollback somehow..?
-            for_acct.accept_block(&block)?;
-            return Ok(hash.hash);
+        let for_acct = match wallet::find_account(self.get_accounts(), acct).await {
+            Some(a) => a,
+            None => return Err("account not found".into()),
+        };
+        let for_acct = &mut *for_acct.lock().await;
+        Manager::receive_receivable(&self.node, for_acct).await
+    }
+
+    // everything from here on, for as long as the manager is around
+    pub fn events(&self) -> broadcast::Receiver<Event> {
+        self.node.events.subscribe()
+    }
+
+    // stops watching for confirmations, waiting until the watcher is gone
+    pub async fn shutdown(&mut self) {
+        self.stop_observing().await;
+    }
+
+    // moves the manager into a background task driven through the returned handle
+    pub fn spawn(self) -> ManagerHandle {
+        let (tx, rx) = mpsc::channel(HANDLE_QUEUE);
+        let events = self.node.events.clone();
+        tokio::spawn(self.serve_handles(rx));
+        ManagerHandle { tx, events }
+    }
+
+    // waits for a block, eg. the hash from send, to be cemented. the websocket
+    // confirmation for our accounts resolves it, block_info is polled meanwhile and
+    // block_confirm is asked for when the election seems stalled.
+    pub fn confirmation(&self, hash: &str, options: ConfirmOptions) -> Confirmation {
+        let node = self.node.clone();
+        let hash = String::from(hash);
+        let (settled, confirmed) = self.confirmed.watch(&hash);
+        let task = tokio::spawn(async move {
+            match settled {
+                Some(outcome) => outcome,
+                None => Manager::wait_confirmed(node, hash, confirmed, options).await,
+            }
+        });
+        Confirmation { task }
+    }
+
+    async fn wait_confirmed(
+        node: Node,
+        hash: String,
+        mut confirmed: broadcast::Receiver<Settled>,
+        options: ConfirmOptions,
+    ) -> Result<(), ConfirmError> {
+        let deadline = time::sleep(options.timeout);
+        tokio::pin!(deadline);
+        let stalled = time::sleep(options.block_confirm_after);
+        tokio::pin!(stalled);
+        let mut polls = time::interval(options.poll_interval);
+        let mut asked = false;
+        loop {
+            tokio::select! {
+                c = confirmed.recv() => match c {
+                    Ok(settled) => {
+                        if let Some(outcome) = settled.outcome(&hash) {
+                            return outcome;
+                        }
+                    }
+                    // lagging behind only means polling has to find it
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => {
+                        return Err(ConfirmError::Aborted(String::from("manager dropped")))
+                    }
+                },
+                _ = polls.tick() => {
+                    if Manager::is_confirmed(&node, &hash).await {
+                        node.settle_published(&hash);
+                        return Ok(());
+                    }
+                }
+                _ = &mut stalled, if !asked => {
+                    asked = true;
+                    if let Err(e) = node.rpc.block_confirm(&hash).await {
+                        let e = format!("block_confirm for {} failed: {}", hash, e);
+                        node.event(Event::Warning(e));
+                    }
+                }
+                _ = &mut deadline => {
+                    return Err(ConfirmError::Timeout {
+                        hash,
+                        after: options.timeout,
+                    })
+                }
+            }
         }
-        Err("could not process send block".into())
     }
 
-    fn get_accounts(&self) -> &Arc<Mutex<Vec<account::Account>>> {
+    // errors count as not confirmed yet
+    async fn is_confirmed(node: &Node, hash: &str) -> bool {
+        match node.block_info(hash).await {
+            Ok(Some(info)) => info.confirmed == "true",
+            Ok(None) => false,
+            Err(e) => {
+                node.event(Event::Warning(e.to_string()));
+                false
+            }
+        }
+    }
+
+    fn get_accounts(&self) -> &wallet::Accounts {
         &self.wallet.as_ref().unwrap().accounts
     }
 
     async fn synchronize(&mut self) -> Result<(), Box<dyn Error>> {
-        let mut accounts = self.get_accounts().lock().await;
-        for a in accounts.iter_mut() {
-            // query nano node and populate ancillary account info
-            if let Some(info) = self.rpc.account_info(&a.addr).await {
-                a.load(info.balance.parse()?, info.frontier, info.representative);
+        Manager::sync(&self.node, self.get_accounts()).await
+    }
+
+    // a handful of batched calls per SYNC_BATCH_SIZE accounts, then receives for up to
+    // SYNC_CONCURRENCY accounts at once. quorum mode checks every account on its own.
+    async fn sync(node: &Node, accounts: &wallet::Accounts) -> Result<(), Box<dyn Error>> {
+        node.event(Event::SyncStarted);
+        let synced = if node.config.quorum.is_some() {
+            Manager::sync_each(node, accounts).await
+        } else {
+            Manager::sync_batched(node, accounts).await
+        };
+        node.event(Event::SyncFinished);
+        synced
+    }
+
+    async fn sync_batched(node: &Node, accounts: &wallet::Accounts) -> Result<(), Box<dyn Error>> {
+        let accounts = wallet::account_locks(accounts).await;
+        let mut receivable: HashMap<String, Vec<(String, raw::Raw)>> = HashMap::new();
+        for chunk in accounts.chunks(SYNC_BATCH_SIZE) {
+            // held until the chunk is loaded, so nothing moves the accounts meanwhile.
+            // always in wallet order, the only order more than one lock is taken in.
+            let mut locked = vec![];
+            for a in chunk {
+                locked.push(a.lock().await);
             }
-            if let Some(pending) = self.rpc.pending(&a.addr).await {
-                if let rpc::RPCPendingBlocks::Blocks(blocks) = pending.blocks {
-                    for hash in blocks {
-                        if let Some(send_block_info) = self.rpc.block_info(&hash).await {
-                            let sent_amount = send_block_info.amount.parse::<raw::Raw>()?;
-                            Manager::receive(&self.rpc, sent_amount, &hash, a).await?;
+            let addrs: Vec<String> = chunk.iter().map(|a| a.addr.clone()).collect();
+            let rpc = &node.rpc;
+            let frontiers = rpc.accounts_frontiers(&addrs).await?.frontiers;
+            let balances = rpc.accounts_balances(&addrs).await?.balances;
+            // the frontier blocks tell us the current representatives
+            let hashes: Vec<String> = frontiers.values().cloned().collect();
+            let heads = if hashes.is_empty() {
+                HashMap::new()
+            } else {
+                rpc.blocks_info(&hashes).await?.blocks
+            };
+            let pending = rpc
+                .accounts_receivable(&addrs, SYNC_RECEIVABLE_COUNT, Some(SYNC_RECEIVE_THRESHOLD))
+                .await?
+                .blocks;
+            for a in locked.iter_mut() {
+                let frontier = frontiers.get(&a.addr);
+                match (frontier, frontier.and_then(|f| heads.get(f))) {
+                    // the balance as of the frontier block. accounts_balances only
+                    // counts confirmed blocks, so it can be from before the frontier.
+                    (Some(frontier), Some(head)) => {
+                        let rep = head.contents.representative.clone();
+                        a.load(head.contents.balance.parse()?, frontier.clone(), rep);
+                    }
+                    // nothing to pair the frontier with, so the chain is not loaded.
+                    // publishing reloads it once the node says we are behind.
+                    (Some(_), None) => {
+                        if let Some(balance) = balances.get(&a.addr) {
+                            a.balance = balance.balance.parse()?;
                         }
                     }
+                    _ if a.frontier == [0u8; block::BLOCK_HASH_SIZE] => {
+                        a.rep = node.config.default_rep.clone();
+                    }
+                    _ => {}
+                }
+                if let Some(blocks) = pending.get(&a.addr) {
+                    let mut sends = vec![];
+                    for p in &blocks.0 {
+                        let amount = p.amount.as_deref().ok_or("receivable without amount")?;
+                        let amount = amount.parse::<raw::Raw>()?;
+                        node.event(Event::IncomingPending {
+                            account: a.addr.clone(),
+                            send: p.hash.clone(),
+                            amount,
+                        });
+                        sends.push((p.hash.clone(), amount));
+                    }
+                    receivable.insert(a.addr.clone(), sends);
                 }
             }
         }
+        let mut receives = vec![];
+        for a in accounts {
+            if let Some(sends) = receivable.remove(&a.addr) {
+                receives.push(async move {
+                    let mut account = a.lock().await;
+                    Manager::receive_all(node, sends, &mut account).await
+                });
+            }
+        }
+        let results: Vec<Result<(), String>> = stream::iter(receives)
+            .buffer_unordered(SYNC_CONCURRENCY)
+            .collect()
+            .await;
+        Ok(results.into_iter().collect::<Result<(), String>>()?)
+    }
+
+    async fn sync_each(node: &Node, accounts: &wallet::Accounts) -> Result<(), Box<dyn Error>> {
+        for a in wallet::account_locks(accounts).await {
+            let a = &mut *a.lock().await;
+            // query nano node and populate ancillary account info
+            if let Some(info) = node.account_info(&a.addr).await? {
+                a.load(info.balance.parse()?, info.frontier, info.representative);
+            } else if a.frontier == [0u8; block::BLOCK_HASH_SIZE] {
+                // unopened, so a zero balance. the open block will use our default
+                // representative
+                a.rep = node.config.default_rep.clone();
+            }
+            Manager::receive_receivable(node, a).await?;
+        }
         Ok(())
     }
 
+    async fn receive_receivable(
+        node: &Node,
+        account: &mut account::Account,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut received = vec![];
+        let receivable = node.rpc.receivable(&account.addr).await?;
+        if let Some(receivable) = receivable {
+            for r in receivable.blocks.0 {
+                // amounts come from block_info so they are checked by the quorum too
+                let info = node.block_info(&r.hash).await?;
+                if let Some(send_block_info) = info {
+                    let sent_amount = send_block_info.amount.parse::<raw::Raw>()?;
+                    node.event(Event::IncomingPending {
+                        account: account.addr.clone(),
+                        send: r.hash.clone(),
+                        amount: sent_amount,
+                    });
+                    received.push(Manager::receive(node, sent_amount, &r.hash, account).await?);
+                }
+            }
+        }
+        Ok(received)
+    }
+
+    // commands that only read the manager run side by side. adding an account waits
+    // for them to finish, and so does shutting down, which also happens once every
+    // handle is dropped.
+    async fn serve_handles(mut self, mut commands: mpsc::Receiver<Command>) {
+        let done = loop {
+            let next = {
+                let manager = &self;
+                let mut running = FuturesUnordered::new();
+                let next = loop {
+                    tokio::select! {
+                        Some(()) = running.next(), if !running.is_empty() => {}
+                        command = commands.recv() => match command {
+                            Some(Command::Shared(request)) => running.push(manager.serve(request)),
+                            Some(Command::Exclusive(exclusive)) => break Some(exclusive),
+                            None => break None,
+                        },
+                    }
+                };
+                while running.next().await.is_some() {}
+                next
+            };
+            match next {
+                Some(Exclusive::AccountAdd { pw, reply }) => {
+                    let added = self.account_add(&pw).await.map_err(|e| e.to_string());
+                    let _ = reply.send(added);
+                }
+                Some(Exclusive::Shutdown(done)) => break Some(done),
+                None => break None,
+            }
+        };
+        self.shutdown().await;
+        if let Some(done) = done {
+            let _ = done.send(());
+        }
+    }
+
+    async fn serve(&self, request: Request) {
+        // errors as strings, they go back to another task
+        match request {
+            Request::Send {
+                amount,
+                from,
+                to,
+                reply,
+            } => {
+                let sent = self.send(amount, &from, &to).await;
+                let _ = reply.send(sent.map_err(|e| e.to_string()));
+            }
+            Request::Change { acct, rep, reply } => {
+                let changed = self.change(&acct, &rep).await;
+                let _ = reply.send(changed.map_err(|e| e.to_string()));
+            }
+            Request::Receive { acct, reply } => {
+                let received = self.receive_pending(&acct).await;
+                let _ = reply.send(received.map_err(|e| e.to_string()));
+            }
+            Request::AccountsInfo { reply } => {
+                let _ = reply.send(Ok(self.get_accounts_info().await));
+            }
+        }
+    }
+
+    async fn stop_observing(&mut self) {
+        self.subscription = None;
+        if let Some(observer) = self.observer.take() {
+            let _ = observer.cancel.send(());
+            let _ = observer.task.await;
+        }
+    }
+
     async fn ws_observe_accounts(&mut self) -> Result<(), Box<dyn Error>> {
         let accounts = self.get_accounts();
-        let addrs = accounts
-            .lock()
+        let addrs = wallet::account_locks(accounts)
             .await
-            .iter()
-            .map(|a| a.addr.clone())
+            .into_iter()
+            .map(|a| a.addr)
             .collect();
         let accounts = accounts.clone();
-        let (tx, rx) = mpsc::channel::<ws::WSConfirmationMessage>(20);
-        let (cancel_tx, cancel_rx) = oneshot::channel();
-        self.cancel = Some(cancel_tx);
-        tokio::spawn(async move {
+        let node = self.node.clone();
+        let confirmed = self.confirmed.clone();
+        let (tx, rx) = mpsc::channel::<ws::WSEvent>(20);
+        let (cancel, cancel_rx) = oneshot::channel();
+        let (subscription, updates) = ws::SubscriptionHandle::new();
+        self.subscription = Some(subscription);
+        let task = tokio::spawn(async move {
             //https://tokio.rs/tokio/tutorial/select#cancellation
             tokio::select! {
                 _ = async {
-                    if let Err(e) = ws::subscribe_confirmation(PUBLIC_NANO_WS_HOST, addrs, tx).await {
-                        eprintln!("ws sub conf error: {:#?}", e)
+                    if let Err(e) = ws::subscribe_confirmation(&node.config.ws_url, addrs, tx, updates, node.config.ws_options()).await {
+                        node.event(Event::Error(format!("websocket subscription failed: {}", e)))
                     }
                 } => {}
 
                 _ = async {
-                    if let Err(e) = Manager::watch_confirmations(accounts, rx).await {
-                        eprintln!("manager watch error: {:#?}", e)
+                    if let Err(e) = Manager::watch_confirmations(node.clone(), accounts, rx, confirmed).await {
+                        node.event(Event::Error(format!("watching confirmations failed: {}", e)))
                     }
                 } => {}
                 _ = cancel_rx => {
@@ -199,91 +1063,494 @@ impl Manager {
                 }
             };
         });
+        self.observer = Some(Observer { cancel, task });
+        Ok(())
+    }
+
+    // errors as strings, so syncing can run in the spawned confirmation watcher
+    async fn receive_all(
+        node: &Node,
+        sends: Vec<(String, raw::Raw)>,
+        account: &mut account::Account,
+    ) -> Result<(), String> {
+        for (hash, amount) in sends {
+            Manager::receive(node, amount, &hash, account)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
         Ok(())
     }
 
     async fn receive(
-        rpc: &rpc::ClientRpc,
+        node: &Node,
         amount: raw::Raw,
         link: &str,
         account: &mut account::Account,
     ) -> Result<String, Box<dyn Error>> {
-        let block: block::NanoBlock;
-        if account.frontier == [0u8; block::BLOCK_HASH_SIZE] {
-            if !account.has_work() {
-                Manager::cache_work(account, rpc, account.pk.clone(), work::RECV_DIFFICULTY)
-                    .await?;
-            }
-            block = account.open(amount, link)?;
-        } else {
-            if !account.has_work() {
-                Manager::cache_work(
-                    account,
-                    rpc,
-                    account.frontier.clone(),
-                    work::RECV_DIFFICULTY,
-                )
-                .await?;
+        let difficulty = account.network.receive_difficulty();
+        let build = |a: &mut account::Account| {
+            if a.frontier == [0u8; block::BLOCK_HASH_SIZE] {
+                a.open(amount, link)
+            } else {
+                a.receive(amount, link)
             }
-            block = account.receive(amount, link)?;
-        }
-        if let Some(hash) = rpc.process(&block).await {
-            // todo: just do this in acct.create_block.
-            // rollback somehow..?
-            account.accept_block(&block)?;
-            return Ok(hash.hash);
-        }
-        Err("could not process receive block".into())
+        };
+        let hash = Manager::publish(node, account, difficulty, build)
+            .await
+            .map_err(|e| format!("could not process receive block: {}", e))?;
+        node.event(Event::Received {
+            account: account.addr.clone(),
+            hash: hash.clone(),
+            send: String::from(link),
+            amount,
+        });
+        Ok(hash)
     }
 
     async fn watch_confirmations(
+        node: Node,
         accounts: wallet::Accounts,
-        mut rx: mpsc::Receiver<ws::WSConfirmationMessage>,
+        mut rx: mpsc::Receiver<ws::WSEvent>,
+        confirmed: Confirmed,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let rpc = rpc::ClientRpc::new(PUBLIC_NANO_RPC_HOST).unwrap();
-        while let Some(msg) = rx.recv().await {
+        while let Some(event) = rx.recv().await {
+            let msg = match event {
+                ws::WSEvent::Notification(n) => match n.message {
+                    ws::TopicMessage::Confirmation(msg) => msg,
+                    _ => continue,
+                },
+                ws::WSEvent::Disconnected(why) => {
+                    node.event(Event::ConnectionLost(why));
+                    continue;
+                }
+                ws::WSEvent::Error(e) => {
+                    node.event(Event::Warning(e));
+                    continue;
+                }
+                // anything sent to us while disconnected is still receivable
+                ws::WSEvent::Reconnected => {
+                    node.event(Event::ConnectionRestored);
+                    if let Err(e) = Manager::sync(&node, &accounts).await {
+                        let e = format!("rescan after reconnecting failed: {}", e);
+                        node.event(Event::Error(e));
+                    }
+                    continue;
+                }
+            };
             //println!("\n\nfrom recv:\n\n{:#?}", msg);
-            let amount = msg.amount.parse::<raw::Raw>().unwrap();
+            confirmed.add(Settled::Confirmed(msg.hash.clone()));
+            let amount = match msg.amount.parse::<raw::Raw>() {
+                Ok(a) => a,
+                // one odd confirmation is no reason to stop watching
+                Err(e) => {
+                    let e = format!(
+                        "bad amount {:?} in confirmation {}: {}",
+                        msg.amount, msg.hash, e
+                    );
+                    node.event(Event::Error(e));
+                    continue;
+                }
+            };
             let hash = msg.hash.as_str();
-            if let block::SubType::Send = msg.block.subtype.unwrap() {
-                let to_addr = msg.block.link_as_account.unwrap();
-                let accounts = &mut *accounts.lock().await;
-                if let Some(account) = accounts.iter_mut().find(|a| a.addr == to_addr) {
-                    Manager::receive(&rpc, amount, hash, account).await.unwrap();
+            // we subscribe with the block included
+            let block = match &msg.block {
+                Some(b) => b,
+                None => continue,
+            };
+            if let Some(account) = wallet::find_account(&accounts, &msg.account).await {
+                if !matches!(block.subtype, Some(block::SubType::Epoch)) {
+                    let account = &mut *account.lock().await;
+                    Manager::check_ours(&node, &confirmed, account, hash, block).await;
+                }
+                node.event(Event::Confirmed {
+                    account: msg.account.clone(),
+                    hash: msg.hash.clone(),
+                });
+            }
+            // both come from the node, anything we cannot make sense of is skipped
+            match block.subtype {
+                Some(block::SubType::Send) => {
+                    let to_addr = match &block.link_as_account {
+                        Some(a) => a.clone(),
+                        None => {
+                            let w = format!("send {} confirmed without link_as_account", hash);
+                            node.event(Event::Warning(w));
+                            continue;
+                        }
+                    };
+                    if let Some(account) = wallet::find_account(&accounts, &to_addr).await {
+                        node.event(Event::IncomingPending {
+                            account: to_addr,
+                            send: msg.hash.clone(),
+                            amount,
+                        });
+                        // in its own task so a busy account doesn't hold up the feed
+                        let node = node.clone();
+                        let hash = hash.to_string();
+                        tokio::spawn(async move {
+                            let account = &mut *account.lock().await;
+                            if let Err(e) = Manager::receive(&node, amount, &hash, account).await {
+                                let e = format!("receiving {} failed: {}", hash, e);
+                                node.event(Event::Error(e));
+                            }
+                        });
+                    }
                 }
+                Some(block::SubType::Epoch) => {
+                    if let Some(account) = wallet::find_account(&accounts, &msg.account).await {
+                        let account = &mut *account.lock().await;
+                        let rejected = Manager::accept_epoch(&node, account, hash, block)
+                            .err()
+                            .map(|e| e.to_string());
+                        // likely our view of the account is behind, the node knows better
+                        if let Some(e) = rejected {
+                            node.event(Event::Error(format!(
+                                "epoch block {} on {} not accepted: {}",
+                                hash, account.addr, e
+                            )));
+                            if let Err(e) = node.resync(account).await {
+                                let e = format!("reloading {} failed: {}", account.addr, e);
+                                node.event(Event::Error(e));
+                            }
+                        }
+                    }
+                }
+                _ => {}
             }
         }
         Ok(())
     }
 
+    // a block confirmed on one of our chains. when it is not the one we published
+    // there, ours lost a fork (or the seed is in use elsewhere) and the account is
+    // reloaded from the node.
+    async fn check_ours(
+        node: &Node,
+        confirmed: &Confirmed,
+        account: &mut account::Account,
+        hash: &str,
+        block: &block::NanoBlock,
+    ) {
+        let ours = node.take_published(&account.addr, &block.previous);
+        // whichever block won, the one before it is cemented
+        node.settle_published(&block.previous);
+        let known = ours
+            .as_deref()
+            .unwrap_or(&hex::encode(account.frontier))
+            .to_uppercase();
+        if known == hash.to_uppercase() {
+            return;
+        }
+        if let Some(ours) = ours {
+            node.event(Event::Error(format!(
+                "block {} on {} lost to the competing block {}",
+                ours, account.addr, hash
+            )));
+            let by = String::from(hash);
+            confirmed.add(Settled::Replaced { ours, by });
+        }
+        if let Err(e) = node.resync(account).await {
+            let e = format!("reloading {} failed: {}", account.addr, e);
+            node.event(Event::Error(e));
+        }
+    }
+
+    // epoch blocks are created by the network's epoch signer on our account chains.
+    // they only move the frontier, so accept them once we know they are genuine.
+    fn accept_epoch(
+        node: &Node,
+        account: &mut account::Account,
+        hash: &str,
+        block: &block::NanoBlock,
+    ) -> Result<(), Box<dyn Error>> {
+        let network = node.config.network;
+        let signer = network
+            .epoch_version(&block.link)
+            .and_then(|v| network.epoch_signer(v))
+            .ok_or("epoch block with unknown link")?;
+        let sig = block.signature.as_deref().ok_or("epoch block not signed")?;
+        if !account::verify_signature(signer, hash, sig)? {
+            return Err("epoch block not signed by the epoch signer".into());
+        }
+        if !block
+            .previous
+            .eq_ignore_ascii_case(&hex::encode(account.frontier))
+        {
+            return Err("epoch block does not follow our frontier".into());
+        }
+        let mut block = block.clone();
+        block.hash = Some(String::from(hash));
+        account.accept_block(&block)
+    }
+
+    // builds a block on the account's frontier and publishes it. when the node says
+    // the account has moved on (a fork, or a block we have not seen) the account is
+    // reloaded from the node and the block built once more on the real frontier.
+    async fn publish<F>(
+        node: &Node,
+        account: &mut account::Account,
+        difficulty: &str,
+        build: F,
+    ) -> Result<String, Box<dyn Error>>
+    where
+        F: Fn(&mut account::Account) -> Result<block::NanoBlock, Box<dyn Error>>,
+    {
+        let mut retried = false;
+        loop {
+            let root = if account.frontier == [0u8; block::BLOCK_HASH_SIZE] {
+                account.pk
+            } else {
+                account.frontier
+            };
+            Manager::ensure_work(account, node, root, difficulty).await?;
+            let block = build(account)?;
+            // as a string, the confirmation watcher needs Send futures
+            let e = match node.publish(account, &block).await {
+                Ok(hash) => return Ok(hash),
+                Err(e) if !Manager::out_of_sync(&*e) => return Err(e),
+                Err(e) => e.to_string(),
+            };
+            if retried {
+                return Err(format!(
+                    "{}. {} is still out of sync after reloading it from the node",
+                    e, account.addr
+                )
+                .into());
+            }
+            if let Err(re) = node.resync(account).await {
+                return Err(format!("{}. reloading {} failed: {}", e, account.addr, re).into());
+            }
+            retried = true;
+        }
+    }
+
+    // the node has blocks for the account that we do not
+    fn out_of_sync(e: &(dyn Error + 'static)) -> bool {
+        matches!(
+            e.downcast_ref::<rpc::NodeError>(),
+            Some(rpc::NodeError::Fork)
+                | Some(rpc::NodeError::GapPrevious)
+                | Some(rpc::NodeError::Old)
+        )
+    }
+
+    // cached work may be stale if the frontier moved underneath it (eg. after a sync),
+    // so check it against the root before it goes into a block.
+    async fn ensure_work(
+        account: &mut account::Account,
+        node: &Node,
+        root: [u8; 32],
+        difficulty: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        if let Some(w) = account.cached_work() {
+            if work::meets_difficulty(&root, w, difficulty)? {
+                return Ok(());
+            }
+            account.clear_work();
+        }
+        Manager::cache_work(account, node, root, difficulty).await
+    }
+
     async fn cache_work(
         account: &mut account::Account,
-        rpc: &rpc::ClientRpc,
+        node: &Node,
         previous: [u8; 32],
         difficulty: &str,
     ) -> Result<(), Box<dyn Error>> {
-        let work = Manager::gen_work(rpc, previous, difficulty).await?;
+        let work = Manager::gen_work(node, previous, difficulty).await?;
         account.cache_work(work);
         Ok(())
     }
 
+    // work from a remote provider is validated against the root before use. if the
+    // provider keeps returning bad work (or none) we fall back to generating it locally.
     async fn gen_work(
-        rpc: &rpc::ClientRpc,
+        node: &Node,
         previous: [u8; 32],
         difficulty: &str,
     ) -> Result<String, Box<dyn Error>> {
         // https://docs.nano.org/integration-guides/work-generation/#work-calculation-details
-        let difficulty = hex::decode(difficulty)?.as_slice().try_into()?;
-        let work;
-        if WORK_LOCAL {
-            work = hex::encode(work::pow_local(previous, &difficulty)?)
-        } else {
+        if node.config.work == config::WorkSource::Node {
             let prev = hex::encode(previous);
-            work = match rpc.work_generate(&prev).await {
-                Some(w) => w.work,
-                None => return Err("failed to generate work".into()),
+            let accept = |url: &str, w: Result<&rpc::RPCWorkGenResp, String>| match w {
+                // malformed work is as bad as work below the threshold
+                Ok(w)
+                    if work::meets_difficulty(&previous, &w.work, difficulty).unwrap_or(false) =>
+                {
+                    true
+                }
+                Ok(w) => {
+                    let w = format!(
+                        "rejected invalid work {} for root {} from {}",
+                        w.work, prev, url
+                    );
+                    node.event(Event::Warning(w));
+                    false
+                }
+                Err(e) => {
+                    node.event(Event::Warning(format!(
+                        "work_generate on {} failed: {}",
+                        url, e
+                    )));
+                    false
+                }
             };
+            if let Some(w) = node
+                .work_rpc
+                .work_generate_until(&prev, difficulty, accept)
+                .await
+            {
+                return Ok(w.work);
+            }
+            node.event(Event::Warning(format!(
+                "no valid work from any work provider for root {}, generating it locally",
+                prev
+            )));
+        }
+        let threshold: [u8; 8] = hex::decode(difficulty)?.as_slice().try_into()?;
+        let work = tokio::task::spawn_blocking(move || {
+            work::pow_local(previous, &threshold)
+                .map(hex::encode)
+                .map_err(|e| e.to_string())
+        })
+        .await??;
+        if !work::meets_difficulty(&previous, &work, difficulty)? {
+            return Err("failed to generate valid work".into());
         }
         Ok(work)
     }
 }
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::network::Network;
+    use crate::rpc::tests::fixture_node;
+    use serde_json::json;
+    use std::sync::atomic::Ordering;
+
+    const TEST_SEED: [u8; 32] = [
+        137, 197, 104, 229, 75, 120, 185, 178, 9, 190, 248, 22, 140, 246, 140, 143, 247, 174, 97,
+        154, 204, 80, 167, 39, 121, 67, 35, 190, 48, 60, 244, 11,
+    ];
+    const OPENED: &str = "nano_1e69ju7uc6eu3zkgm3krmu9x7hejdnx8sgkaah3ywo5xws6ttcy1g4yeo4bi";
+    const UNOPENED: &str = "nano_1xc8qp4qod9jaj5ooqtwjeq5qf4pnmnincqcqyctkuqfranft5j7narjhrwr";
+    const REP: &str = "nano_16u1uufyoig8777y6r8iqjtrw8sg8maqrm36zzcm95jmbd9i9aj5i8abr8u5";
+
+    fn node(action: &str) -> (u16, String) {
+        let frontier = "AB".repeat(32);
+        let body = match action {
+            "accounts_frontiers" => json!({ "frontiers": { OPENED: frontier } }),
+            "accounts_balances" => json!({ "balances": {
+                OPENED: { "balance": "1000", "pending": "5" },
+                UNOPENED: { "balance": "0", "pending": "7" },
+            }}),
+            "blocks_info" => json!({ "blocks": { frontier.clone(): {
+                "block_account": OPENED, "amount": "1000", "balance": "1000", "height": "1",
+                "local_timestamp": "0", "confirmed": "true", "subtype": "open",
+                "contents": {
+                    "type": "state", "account": OPENED, "previous": "0".repeat(64),
+                    "representative": REP, "balance": "1000", "link": "CD".repeat(32),
+                    "work": "0000000000000000",
+                },
+            }}}),
+            "accounts_receivable" => json!({ "blocks": {
+                OPENED: { "01".repeat(32): { "amount": "5", "source": UNOPENED } },
+                UNOPENED: { "02".repeat(32): { "amount": "7", "source": OPENED } },
+            }}),
+            "process" => json!({ "hash": "03".repeat(32) }),
+            _ => json!({ "error": "Unknown command" }),
+        };
+        (200, body.to_string())
+    }
+
+    async fn test_manager(url: &str, accounts: u32) -> Manager {
+        let mut config = config::ManagerConfig::new(Network::Dev);
+        config.rpc_url = String::from(url);
+        config.work = config::WorkSource::Local;
+        let mut m = Manager::new(config).await.unwrap();
+        let accounts = (0..accounts)
+            .map(|i| account::Account::new(i, &TEST_SEED, Network::Dev).unwrap())
+            .collect();
+        m.wallet = Some(wallet::Wallet {
+            name: String::from("test"),
+            network: Network::Dev,
+            accounts: wallet::accounts(accounts),
+        });
+        m
+    }
+
+    #[tokio::test]
+    async fn synchronize_batches_rpc_calls() {
+        let (url, hits) = fixture_node(node).await;
+        let mut m = test_manager(&url, 2).await;
+        m.synchronize().await.unwrap();
+        // frontiers, balances, blocks_info and accounts_receivable, then one process per receive
+        assert_eq!(hits.load(Ordering::SeqCst), 6);
+        let accounts = wallet::account_locks(m.get_accounts()).await;
+        let (a, b) = (accounts[0].lock().await, accounts[1].lock().await);
+        assert_eq!(a.balance, raw::Raw::new(1005));
+        assert_eq!(a.rep, REP);
+        assert_eq!(b.balance, raw::Raw::new(7));
+        assert_eq!(b.rep, account::DEFAULT_REP);
+    }
+
+    #[tokio::test]
+    async fn accounts_are_locked_separately() {
+        let (url, _) = fixture_node(node).await;
+        let mut m = test_manager(&url, 2).await;
+        m.synchronize().await.unwrap();
+        let accounts = wallet::account_locks(m.get_accounts()).await;
+        let busy = accounts[1].lock().await;
+        let amount = raw::Raw::new(1).to_mnano();
+        let send = m.send(amount.clone(), OPENED, UNOPENED);
+        tokio::time::timeout(Duration::from_secs(5), send)
+            .await
+            .unwrap()
+            .unwrap();
+        // the same account waits its turn
+        let held = accounts[0].lock().await;
+        let send = m.send(amount, OPENED, UNOPENED);
+        assert!(tokio::time::timeout(Duration::from_millis(100), send)
+            .await
+            .is_err());
+        drop((busy, held));
+    }
+
+    #[test]
+    fn settling_a_block_forgets_the_ones_before_it() {
+        let node = Node::new(config::ManagerConfig::new(Network::Dev)).unwrap();
+        {
+            let mut published = node.published.lock().unwrap();
+            for (previous, hash) in [("01", "02"), ("02", "03"), ("03", "04")].iter() {
+                let root = (String::from(OPENED), previous.repeat(32));
+                published.insert(root, hash.repeat(32));
+            }
+        }
+        node.settle_published(&"03".repeat(32));
+        assert_eq!(node.published.lock().unwrap().len(), 1);
+        assert_eq!(
+            node.take_published(OPENED, &"03".repeat(32)),
+            Some("04".repeat(32))
+        );
+    }
+
+    #[tokio::test]
+    async fn publish_drops_work_the_node_refuses() {
+        let (url, _) = fixture_node(|_| {
+            (
+                200,
+                json!({ "error": "Block work is insufficient" }).to_string(),
+            )
+        })
+        .await;
+        let m = test_manager(&url, 1).await;
+        let mut a = account::Account::new(0, &TEST_SEED, Network::Dev).unwrap();
+        a.cache_work(String::from("0000000000000000"));
+        let block = a.open(raw::Raw::new(1), &"01".repeat(32)).unwrap();
+        assert!(m.node.publish(&mut a, &block).await.is_err());
+        assert!(!a.has_work());
+        assert_eq!(a.frontier, [0u8; 32]);
+    }
+}
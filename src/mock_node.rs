@@ -0,0 +1,424 @@
+// a stand-in nano node for tests: rpc over http and the websocket topics, on
+// local ports. each rpc action is answered with a canned response or one scripted
+// from the request. actions without a response get "Unknown command" like a real node.
+// https://docs.nano.org/commands/rpc-protocol/
+// https://docs.nano.org/integration-guides/websockets/
+use crate::ledger::{self, Ledger};
+use futures_util::{SinkExt, StreamExt};
+use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::{accept_async, tungstenite::Message};
+
+type Handler = Arc<dyn Fn(&Value) -> Value + Send + Sync>;
+
+pub struct MockNode {
+    rpc_url: String,
+    ws_url: String,
+    state: Arc<Mutex<MockState>>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+#[derive(Default)]
+struct MockState {
+    handlers: HashMap<String, Handler>,
+    requests: Vec<Value>,
+    last_session: usize,
+    // websocket requests go unanswered, like a node that hung
+    muted: bool,
+    // why the rpc server stopped, if it did
+    rpc_error: Option<String>,
+    // websocket connections by id. None closes the connection.
+    sessions: HashMap<usize, mpsc::UnboundedSender<Option<String>>>,
+    subscribers: HashMap<usize, Subscriber>,
+    // other topics each connection subscribed to
+    topics: HashMap<usize, HashSet<String>>,
+}
+
+// a websocket client subscribed to confirmations
+struct Subscriber {
+    // None when subscribed to every account
+    accounts: Option<HashSet<String>>,
+    tx: mpsc::UnboundedSender<Option<String>>,
+}
+
+impl MockNode {
+    pub async fn start() -> Result<MockNode, Box<dyn Error>> {
+        let state = Arc::new(Mutex::new(MockState::default()));
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let rpc_url = format!("http://{}", listener.local_addr()?);
+        let s = state.clone();
+        let make_svc = make_service_fn(move |_| {
+            let s = s.clone();
+            async move { Ok::<_, Infallible>(service_fn(move |req| handle(s.clone(), req))) }
+        });
+        let server = Server::from_tcp(listener)?.serve(make_svc);
+        let s = state.clone();
+        let rpc = tokio::spawn(async move {
+            if let Err(e) = server.await {
+                s.lock().unwrap().rpc_error = Some(e.to_string());
+            }
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let ws_url = format!("ws://{}", listener.local_addr()?);
+        let s = state.clone();
+        let ws = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(ws_session(s.clone(), stream));
+            }
+        });
+        Ok(MockNode {
+            rpc_url,
+            ws_url,
+            state,
+            tasks: vec![rpc, ws],
+        })
+    }
+
+    pub fn rpc_url(&self) -> &str {
+        &self.rpc_url
+    }
+
+    pub fn ws_url(&self) -> &str {
+        &self.ws_url
+    }
+
+    // answers `action` with `body` until told otherwise
+    pub fn respond(&self, action: &str, body: Value) {
+        self.respond_with(action, move |_| body.clone());
+    }
+
+    // answers `action` with whatever `f` makes of the request
+    pub fn respond_with<F>(&self, action: &str, f: F)
+    where
+        F: Fn(&Value) -> Value + Send + Sync + 'static,
+    {
+        let mut state = self.state.lock().unwrap();
+        state.handlers.insert(String::from(action), Arc::new(f));
+    }
+
+    // canned responses from a directory of <action>.json files, eg. tests/fixtures/rpc
+    pub fn respond_from_dir<P: AsRef<Path>>(&self, dir: P) -> Result<(), Box<dyn Error>> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let action = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .ok_or("bad fixture file name")?;
+            let body = serde_json::from_str(&fs::read_to_string(&path)?)?;
+            self.respond(action, body);
+        }
+        Ok(())
+    }
+
+    // answers the actions the ledger knows from it, and sends a websocket confirmation
+    // for every block it accepts. the ledger is shared so tests can fund accounts and
+    // look at balances.
+    pub fn serve_ledger(&self, mut ledger: Ledger) -> Arc<Mutex<Ledger>> {
+        let state = self.state.clone();
+        ledger.on_confirmation(move |message| {
+            broadcast(&state, message);
+        });
+        let ledger = Arc::new(Mutex::new(ledger));
+        for action in ledger::ACTIONS {
+            let l = ledger.clone();
+            self.respond_with(action, move |r| l.lock().unwrap().handle(r));
+        }
+        ledger
+    }
+
+    // requests received for `action`, oldest first
+    pub fn requests(&self, action: &str) -> Vec<Value> {
+        let state = self.state.lock().unwrap();
+        state
+            .requests
+            .iter()
+            .filter(|r| r["action"] == action)
+            .cloned()
+            .collect()
+    }
+
+    // set when the rpc server failed, for a test that stopped getting answers
+    pub fn rpc_error(&self) -> Option<String> {
+        self.state.lock().unwrap().rpc_error.clone()
+    }
+
+    // whether a websocket client gets confirmations for `account`
+    pub fn subscribed(&self, account: &str) -> bool {
+        let state = self.state.lock().unwrap();
+        state.subscribers.values().any(|s| s.wants(account))
+    }
+
+    pub async fn wait_subscribed(&self, account: &str, timeout: Duration) -> Result<(), String> {
+        let waiting = async {
+            while !self.subscribed(account) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        tokio::time::timeout(timeout, waiting)
+            .await
+            .map_err(|_| format!("no confirmation subscription for {}", account))
+    }
+
+    // stops answering websocket requests (no acks, pongs or subscriptions) while the
+    // connections stay open
+    pub fn mute(&self, muted: bool) {
+        self.state.lock().unwrap().muted = muted;
+    }
+
+    // websocket connections accepted so far
+    pub fn connections(&self) -> usize {
+        self.state.lock().unwrap().last_session
+    }
+
+    // websocket connections still open
+    pub fn open_connections(&self) -> usize {
+        self.state.lock().unwrap().sessions.len()
+    }
+
+    // closes every websocket connection, like a node restart or a network blip
+    pub fn disconnect_all(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.subscribers.clear();
+        state.topics.clear();
+        for (_, tx) in state.sessions.drain() {
+            let _ = tx.send(None);
+        }
+    }
+
+    // sends a confirmation to the clients subscribed to its account or, for sends,
+    // to the receiving account. returns how many got it.
+    pub fn confirm(&self, message: Value) -> usize {
+        broadcast(&self.state, message)
+    }
+
+    // sends a message on any other topic to the clients subscribed to it. returns
+    // how many got it.
+    pub fn publish(&self, topic: &str, message: Value) -> usize {
+        let out = json!({
+            "topic": topic,
+            "time": now_millis(),
+            "message": message,
+        })
+        .to_string();
+        let state = self.state.lock().unwrap();
+        state
+            .topics
+            .iter()
+            .filter(|(_, topics)| topics.contains(topic))
+            .filter_map(|(id, _)| state.sessions.get(id))
+            .filter(|tx| tx.send(Some(out.clone())).is_ok())
+            .count()
+    }
+}
+
+fn broadcast(state: &Mutex<MockState>, message: Value) -> usize {
+    let account = message["account"].as_str().unwrap_or_default();
+    let to = message["block"]["link_as_account"]
+        .as_str()
+        .unwrap_or_default();
+    let out = json!({
+        "topic": "confirmation",
+        "time": now_millis(),
+        "message": message,
+    })
+    .to_string();
+    let state = state.lock().unwrap();
+    state
+        .subscribers
+        .values()
+        .filter(|s| s.wants(account) || s.wants(to))
+        .filter(|s| s.tx.send(Some(out.clone())).is_ok())
+        .count()
+}
+
+impl Drop for MockNode {
+    fn drop(&mut self) {
+        for t in &self.tasks {
+            t.abort();
+        }
+    }
+}
+
+impl Subscriber {
+    fn wants(&self, account: &str) -> bool {
+        match &self.accounts {
+            Some(accounts) => accounts.contains(account),
+            None => !account.is_empty(),
+        }
+    }
+}
+
+async fn handle(
+    state: Arc<Mutex<MockState>>,
+    req: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    let body = hyper::body::to_bytes(req.into_body())
+        .await
+        .unwrap_or_default();
+    let resp = match serde_json::from_slice::<Value>(&body) {
+        Ok(r) => {
+            let handler = {
+                let mut state = state.lock().unwrap();
+                state.requests.push(r.clone());
+                r["action"]
+                    .as_str()
+                    .and_then(|a| state.handlers.get(a).cloned())
+            };
+            match handler {
+                Some(h) => h(&r),
+                None => json!({ "error": "Unknown command" }),
+            }
+        }
+        Err(_) => json!({ "error": "Unable to parse JSON" }),
+    };
+    let mut resp = Response::new(Body::from(resp.to_string()));
+    resp.headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    Ok(resp)
+}
+
+async fn ws_session(state: Arc<Mutex<MockState>>, stream: TcpStream) {
+    let ws = match accept_async(stream).await {
+        Ok(ws) => ws,
+        Err(_) => return,
+    };
+    let (mut write, mut read) = ws.split();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let id = {
+        let mut state = state.lock().unwrap();
+        state.last_session += 1;
+        let id = state.last_session;
+        state.sessions.insert(id, tx.clone());
+        id
+    };
+    loop {
+        tokio::select! {
+            msg = read.next() => match msg {
+                Some(Ok(Message::Text(_))) if state.lock().unwrap().muted => {}
+                Some(Ok(Message::Text(text))) => {
+                    if let Some(reply) = ws_request(&state, id, &tx, &text) {
+                        if write.send(Message::text(reply)).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+                Some(Ok(_)) => {}
+                _ => break,
+            },
+            Some(out) = rx.recv() => match out {
+                Some(out) => {
+                    if write.send(Message::text(out)).await.is_err() {
+                        break;
+                    }
+                }
+                None => {
+                    let _ = write.send(Message::Close(None)).await;
+                    break;
+                }
+            }
+        }
+    }
+    let mut state = state.lock().unwrap();
+    state.subscribers.remove(&id);
+    state.topics.remove(&id);
+    state.sessions.remove(&id);
+}
+
+// handles a websocket action, returning the reply if there is one
+fn ws_request(
+    state: &Mutex<MockState>,
+    id: usize,
+    tx: &mpsc::UnboundedSender<Option<String>>,
+    text: &str,
+) -> Option<String> {
+    let req: Value = serde_json::from_str(text).ok()?;
+    let action = req["action"].as_str()?;
+    match (action, req["topic"].as_str()) {
+        ("subscribe", Some("confirmation")) => {
+            let accounts = req["options"]["accounts"].as_array().map(|a| {
+                a.iter()
+                    .filter_map(|a| a.as_str().map(String::from))
+                    .collect()
+            });
+            let subscriber = Subscriber {
+                accounts,
+                tx: tx.clone(),
+            };
+            state.lock().unwrap().subscribers.insert(id, subscriber);
+        }
+        ("update", Some("confirmation")) => {
+            let list = |k: &str| -> Vec<String> {
+                req["options"][k]
+                    .as_array()
+                    .map(|a| {
+                        a.iter()
+                            .filter_map(|a| a.as_str().map(String::from))
+                            .collect()
+                    })
+                    .unwrap_or_default()
+            };
+            let mut state = state.lock().unwrap();
+            // subscribers to every account have nothing to update
+            if let Some(Subscriber {
+                accounts: Some(accounts),
+                ..
+            }) = state.subscribers.get_mut(&id)
+            {
+                accounts.extend(list("accounts_add"));
+                for a in list("accounts_del") {
+                    accounts.remove(&a);
+                }
+            }
+        }
+        ("unsubscribe", Some("confirmation")) => {
+            state.lock().unwrap().subscribers.remove(&id);
+        }
+        ("subscribe", Some(topic)) => {
+            let mut state = state.lock().unwrap();
+            let topics = state.topics.entry(id).or_default();
+            topics.insert(String::from(topic));
+        }
+        ("unsubscribe", Some(topic)) => {
+            if let Some(topics) = state.lock().unwrap().topics.get_mut(&id) {
+                topics.remove(topic);
+            }
+        }
+        ("ping", _) => return Some(ack("pong", &req)),
+        _ => return None,
+    }
+    if req["ack"].as_bool() == Some(true) {
+        return Some(ack(action, &req));
+    }
+    None
+}
+
+fn ack(action: &str, req: &Value) -> String {
+    let mut ack = json!({ "ack": action, "time": now_millis() });
+    if let Some(id) = req.get("id") {
+        ack["id"] = id.clone();
+    }
+    ack.to_string()
+}
+
+fn now_millis() -> String {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+        .to_string()
+}
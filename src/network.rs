@@ -0,0 +1,157 @@
+// per-network parameters. addresses are encoded the same way (nano_) on every network.
+// https://docs.nano.org/running-a-node/beta-network/
+// https://docs.nano.org/integration-guides/work-generation/#difficulty-thresholds
+use crate::block;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+const LIVE_GENESIS: &str = "nano_3t6k35gi95xu6tergt6p69ck76ogmitsa8mnijtpxm9fkcm736xtoncuohr3";
+const LIVE_EPOCH_V2_SIGNER: &str =
+    "nano_3qb6o6i1tkzr6jwr5s7eehfxwg9x6eemitdinbpi7u8bjjwsgqfj4wzser3x";
+const BETA_GENESIS: &str = "nano_1betagoxpxwykx4kw86dnhosc8t3s7ix8eeentwkcg1hbpez1outjrcyg4n1";
+const DEV_GENESIS: &str = "nano_3e3j5tkog48pnny9dmfzj1r16pg8t1e76dz5tmac6iq689wyjfpiij4txtdo";
+// the dev genesis key is public, so private dev networks can be funded from it
+pub const DEV_GENESIS_KEY: &str =
+    "34F0A37AAD20F4A260F0A5B3CB3D7FB50673212263E58A380BC10474BB039CE4";
+
+// "epoch v1 block" and "epoch v2 block" in ascii, zero padded
+const EPOCH_V1_LINK: &str = "65706F636820763120626C6F636B000000000000000000000000000000000000";
+const EPOCH_V2_LINK: &str = "65706F636820763220626C6F636B000000000000000000000000000000000000";
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    #[default]
+    Live,
+    Beta,
+    Dev,
+}
+
+impl Network {
+    // epoch 2 threshold for send and change blocks
+    pub fn send_difficulty(&self) -> &'static str {
+        match self {
+            Network::Live => "fffffff800000000",
+            Network::Beta => "fffff00000000000",
+            Network::Dev => "ffc0000000000000",
+        }
+    }
+
+    // epoch 2 threshold for open, receive and epoch blocks
+    pub fn receive_difficulty(&self) -> &'static str {
+        match self {
+            Network::Live => "fffffe0000000000",
+            Network::Beta => "ffffe00000000000",
+            Network::Dev => "f000000000000000",
+        }
+    }
+
+    pub fn difficulty_for(&self, subtype: block::SubType) -> &'static str {
+        match subtype {
+            block::SubType::Send | block::SubType::Change => self.send_difficulty(),
+            block::SubType::Receive | block::SubType::Open | block::SubType::Epoch => {
+                self.receive_difficulty()
+            }
+        }
+    }
+
+    pub fn genesis_account(&self) -> &'static str {
+        match self {
+            Network::Live => LIVE_GENESIS,
+            Network::Beta => BETA_GENESIS,
+            Network::Dev => DEV_GENESIS,
+        }
+    }
+
+    // epoch blocks are signed by the genesis account, except v2 on the live network
+    pub fn epoch_signer(&self, version: u8) -> Option<&'static str> {
+        match (self, version) {
+            (Network::Live, 2) => Some(LIVE_EPOCH_V2_SIGNER),
+            (_, 1) | (_, 2) => Some(self.genesis_account()),
+            _ => None,
+        }
+    }
+
+    pub fn epoch_version(&self, link: &str) -> Option<u8> {
+        if link.eq_ignore_ascii_case(EPOCH_V1_LINK) {
+            Some(1)
+        } else if link.eq_ignore_ascii_case(EPOCH_V2_LINK) {
+            Some(2)
+        } else {
+            None
+        }
+    }
+
+    // wallets are kept in a separate file per network so they never mix
+    pub fn wallet_file(&self) -> &'static str {
+        match self {
+            Network::Live => "nanors.wal",
+            Network::Beta => "nanors.beta.wal",
+            Network::Dev => "nanors.dev.wal",
+        }
+    }
+
+    //other good nodes "https://mynano.ninja/api/node";
+    // there are no public beta or dev nodes, so those expect a local node on the
+    // network's default ports.
+    pub fn default_rpc_url(&self) -> &'static str {
+        match self {
+            Network::Live => "https://proxy.nanos.cc/proxy",
+            Network::Beta => "http://127.0.0.1:55000",
+            Network::Dev => "http://127.0.0.1:45000",
+        }
+    }
+
+    pub fn default_ws_url(&self) -> &'static str {
+        match self {
+            Network::Live => "wss://ws.mynano.ninja/",
+            Network::Beta => "ws://127.0.0.1:57000",
+            Network::Dev => "ws://127.0.0.1:47000",
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "live" => Ok(Network::Live),
+            "beta" => Ok(Network::Beta),
+            "dev" => Ok(Network::Dev),
+            _ => Err(format!("unknown network {}. expected live, beta or dev", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for Network {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let s = match self {
+            Network::Live => "live",
+            Network::Beta => "beta",
+            Network::Dev => "dev",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::work;
+
+    #[test]
+    fn live_thresholds_match_work_defaults() {
+        assert_eq!(Network::Live.send_difficulty(), work::DEFAULT_DIFFICULTY);
+        assert_eq!(Network::Live.receive_difficulty(), work::RECV_DIFFICULTY);
+    }
+
+    #[test]
+    fn detects_epoch_links() {
+        let n = Network::Live;
+        assert_eq!(n.epoch_version(&EPOCH_V2_LINK.to_lowercase()), Some(2));
+        assert_eq!(n.epoch_signer(2), Some(LIVE_EPOCH_V2_SIGNER));
+        assert_eq!(Network::Dev.epoch_signer(2), Some(DEV_GENESIS));
+        assert_eq!(n.epoch_version(&"0".repeat(64)), None);
+    }
+}
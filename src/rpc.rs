@@ -1,18 +1,234 @@
 // View other options of Public Nano Nodes: https://publicnodes.somenano.com
 // https://docs.nano.org/commands/rpc-protocol/#node-rpcs
 use crate::block;
-use reqwest::Client;
-use serde::{
-    de::{DeserializeOwned, IntoDeserializer},
-    Deserialize, Deserializer, Serialize,
-};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, RETRY_AFTER};
+use reqwest::{Certificate, Client, Proxy, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize};
 use std::array::IntoIter;
 use std::collections::HashMap;
 use std::iter::FromIterator;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
 
+// an endpoint is skipped while it has failed this many times in a row
+const MAX_CONSECUTIVE_FAILURES: u32 = 3;
+// or while its block count trails the best known endpoint by more than this
+const MAX_BLOCK_LAG: u64 = 1000;
+// wait before trying the next endpoint. doubles with every attempt.
+const RETRY_BACKOFF: Duration = Duration::from_millis(100);
+// first node release with the receivable actions. older ones only know pending.
+const RECEIVABLE_MAJOR_VERSION: u32 = 23;
+// a node that never answers should not hang the caller forever
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_USER_AGENT: &str = concat!("nanors/", env!("CARGO_PKG_VERSION"));
+// back off from a rate limited endpoint without a Retry-After. doubles while it
+// keeps limiting us, up to the max.
+const THROTTLE_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_THROTTLE_BACKOFF: Duration = Duration::from_secs(30);
+
+// endpoints are tried in the order given, healthy ones first. health is shared
+// between clones so every user of the client learns from each request.
+#[derive(Clone)]
 pub struct ClientRpc {
-    server_addr: String,
-    client: Box<reqwest::Client>,
+    endpoints: Arc<Vec<Endpoint>>,
+    stats: Arc<Mutex<HashMap<String, ActionStats>>>,
+}
+
+// each endpoint has its own http client, since proxies, certificates and auth differ
+struct Endpoint {
+    url: String,
+    client: Client,
+    health: Mutex<EndpointHealth>,
+    bucket: Option<Mutex<TokenBucket>>,
+    permits: Option<Semaphore>,
+}
+
+// requests sent per rpc action, over all endpoints
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ActionStats {
+    pub requests: u64,
+    // no usable answer (timeouts, http errors, bad json)
+    pub failed: u64,
+    pub rate_limited: u64,
+}
+
+// `rate` requests per second on average, up to `burst` at once
+struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+// the endpoint told us to slow down
+struct Throttled {
+    retry_after: Option<Duration>,
+    msg: String,
+}
+
+// per endpoint connection settings. unset ones fall back to the builder's defaults.
+// tokens and headers are never serialized so they do not end up in printed configs.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct EndpointOptions {
+    pub timeout_secs: Option<u64>,
+    pub connect_timeout_secs: Option<u64>,
+    // sent as `Authorization: Bearer <token>`
+    #[serde(default, skip_serializing)]
+    pub bearer_token: Option<String>,
+    // extra headers, eg. an api key
+    #[serde(default, skip_serializing)]
+    pub headers: HashMap<String, String>,
+    // http://, https:// or socks5:// proxy url
+    pub proxy: Option<String>,
+    // path to a pem certificate to trust in addition to the system roots
+    pub ca_cert: Option<String>,
+    // trust only ca_cert, eg. to pin a self signed node certificate
+    #[serde(default)]
+    pub pin_ca_cert: bool,
+    pub user_agent: Option<String>,
+    // requests per second, averaged over `burst` requests
+    pub rate_limit: Option<f64>,
+    pub burst: Option<u32>,
+    // requests in flight at once
+    pub max_concurrent: Option<usize>,
+}
+
+pub struct ClientRpcBuilder {
+    endpoints: Vec<(String, EndpointOptions)>,
+    timeout: Duration,
+    connect_timeout: Option<Duration>,
+    user_agent: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct EndpointHealth {
+    pub url: String,
+    pub latency: Option<Duration>,
+    pub consecutive_failures: u32,
+    pub block_count: Option<u64>,
+    pub major_version: Option<u32>,
+    // whether the node knows `receivable`, once we have found out
+    pub receivable: Option<bool>,
+    // rate limited responses in a row, and when we may ask again
+    pub rate_limited: u32,
+    pub throttled_until: Option<Instant>,
+}
+
+impl EndpointHealth {
+    // `best` is the highest block count seen across all endpoints
+    pub fn is_healthy(&self, best: Option<u64>) -> bool {
+        let lag = match (best, self.block_count) {
+            (Some(best), Some(count)) => best.saturating_sub(count),
+            _ => 0,
+        };
+        let throttled = self.throttled_until.is_some_and(|t| t > Instant::now());
+        self.consecutive_failures < MAX_CONSECUTIVE_FAILURES && lag <= MAX_BLOCK_LAG && !throttled
+    }
+}
+
+// the `{"error": ...}` a node answers with, for the messages we react to.
+// https://docs.nano.org/commands/rpc-protocol/#process
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodeError {
+    AccountNotFound,
+    BlockNotFound,
+    // another block already has the same previous
+    Fork,
+    // the block is already in the ledger
+    Old,
+    GapPrevious,
+    GapSource,
+    InsufficientWork,
+    BadSignature,
+    NegativeSpend,
+    Unreceivable,
+    BalanceMismatch,
+    InvalidBlock,
+    UnknownCommand,
+    Other(String),
+}
+
+impl NodeError {
+    pub fn from_message(msg: &str) -> NodeError {
+        match msg {
+            "Account not found" => NodeError::AccountNotFound,
+            "Block not found" => NodeError::BlockNotFound,
+            "Fork" => NodeError::Fork,
+            "Old block" => NodeError::Old,
+            "Gap previous block" => NodeError::GapPrevious,
+            "Gap source block" => NodeError::GapSource,
+            // older nodes say "insufficient", newer ones "less than threshold"
+            "Block work is insufficient" | "Block work is less than threshold" => {
+                NodeError::InsufficientWork
+            }
+            "Bad signature" => NodeError::BadSignature,
+            "Negative spend" => NodeError::NegativeSpend,
+            "Unreceivable" => NodeError::Unreceivable,
+            "Balance and amount delta do not match" => NodeError::BalanceMismatch,
+            "Block is invalid" | "Invalid block" => NodeError::InvalidBlock,
+            "Unknown command" => NodeError::UnknownCommand,
+            _ => NodeError::Other(String::from(msg)),
+        }
+    }
+}
+
+impl std::fmt::Display for NodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let msg = match self {
+            NodeError::AccountNotFound => "Account not found",
+            NodeError::BlockNotFound => "Block not found",
+            NodeError::Fork => "Fork",
+            NodeError::Old => "Old block",
+            NodeError::GapPrevious => "Gap previous block",
+            NodeError::GapSource => "Gap source block",
+            NodeError::InsufficientWork => "Block work is insufficient",
+            NodeError::BadSignature => "Bad signature",
+            NodeError::NegativeSpend => "Negative spend",
+            NodeError::Unreceivable => "Unreceivable",
+            NodeError::BalanceMismatch => "Balance and amount delta do not match",
+            NodeError::InvalidBlock => "Block is invalid",
+            NodeError::UnknownCommand => "Unknown command",
+            NodeError::Other(msg) => msg,
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+impl std::error::Error for NodeError {}
+
+// a node that answers with an error has still answered, so that is not retried
+// elsewhere or counted against it.
+enum PostError {
+    Unreachable(String),
+    Rejected(NodeError),
+}
+
+impl PostError {
+    // node errors stay typed so callers can downcast to NodeError
+    fn into_error(self) -> Box<dyn std::error::Error> {
+        match self {
+            PostError::Unreachable(m) => m.into(),
+            PostError::Rejected(e) => e.into(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RPCErrorResp {
+    error: String,
+}
+
+// the answer most endpoints gave. `value` is Err when they agree on a node error,
+// eg. "Account not found".
+#[derive(Debug)]
+pub struct QuorumResp<T> {
+    pub value: Result<T, NodeError>,
+    pub agreeing: usize,
+    pub responded: usize,
+    // endpoints that answered differently
+    pub dissent: Vec<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -30,30 +246,84 @@ pub struct RPCAccountInfoResp {
 }
 
 #[derive(Deserialize, Debug)]
-pub struct RPCPendingResp {
-    //#[serde(deserialize_with = "empty_string_as_none")]
-    pub blocks: RPCPendingBlocks,
+pub struct RPCReceivableResp {
+    pub blocks: RPCReceivableBlocks,
 }
 
-#[derive(Deserialize, Debug)]
-#[serde(untagged)]
-pub enum RPCPendingBlocks {
-    Blocks(Vec<String>),
-    Empty(String),
+// receivable/pending answer with a list of hashes, hash -> amount (with threshold),
+// hash -> {amount, source} (with source) or "" when there is nothing. all end up here.
+#[derive(Debug, Default)]
+pub struct RPCReceivableBlocks(pub Vec<RPCReceivable>);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RPCReceivable {
+    pub hash: String,
+    pub amount: Option<String>,
+    pub source: Option<String>,
 }
 
-// fn empty_string_as_none<'de, D, T>(de: D) -> Result<Option<T>, D::Error>
-// where
-//     D: Deserializer<'de>,
-//     T: Deserialize<'de>,
-// {
-//     let opt = Option::<String>::deserialize(de)?;
-//     let opt = opt.as_ref().map(String::as_str);
-//     match opt {
-//         None | Some("") => Ok(None),
-//         Some(s) => T::deserialize(s.into_deserializer()).map(Some),
-//     }
-// }
+impl<'de> Deserialize<'de> for RPCReceivableBlocks {
+    fn deserialize<D: Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Shape {
+            Hashes(Vec<String>),
+            Sources(HashMap<String, RPCPendingSource>),
+            Amounts(HashMap<String, String>),
+            Empty(String),
+        }
+        let blocks = match Shape::deserialize(de)? {
+            Shape::Hashes(hashes) => hashes
+                .into_iter()
+                .map(|hash| RPCReceivable {
+                    hash,
+                    amount: None,
+                    source: None,
+                })
+                .collect(),
+            Shape::Sources(sources) => sources
+                .into_iter()
+                .map(|(hash, s)| RPCReceivable {
+                    hash,
+                    amount: Some(s.amount),
+                    source: Some(s.source),
+                })
+                .collect(),
+            Shape::Amounts(amounts) => amounts
+                .into_iter()
+                .map(|(hash, amount)| RPCReceivable {
+                    hash,
+                    amount: Some(amount),
+                    source: None,
+                })
+                .collect(),
+            Shape::Empty(s) if s.is_empty() => vec![],
+            Shape::Empty(s) => {
+                return Err(serde::de::Error::custom(format!("unexpected blocks {}", s)))
+            }
+        };
+        Ok(RPCReceivableBlocks(blocks))
+    }
+}
+
+// the node sends "" instead of an empty list or object in a few places
+pub(crate) fn empty_string_as_default<'de, D, T>(de: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OrEmpty<T> {
+        Value(T),
+        Empty(String),
+    }
+    match OrEmpty::<T>::deserialize(de)? {
+        OrEmpty::Value(t) => Ok(t),
+        OrEmpty::Empty(s) if s.is_empty() => Ok(T::default()),
+        OrEmpty::Empty(s) => Err(serde::de::Error::custom(format!("unexpected string {}", s))),
+    }
+}
 
 #[derive(Deserialize, Debug)]
 pub struct RPCWorkGenResp {
@@ -67,12 +337,12 @@ pub struct RPCProcessResp {
 
 #[derive(Deserialize, Debug)]
 pub struct RPCBlockInfoResp {
-    block_account: String,
+    pub block_account: String,
     pub amount: String,
-    balance: String,
-    height: String,
-    local_timestamp: String,
-    confirmed: String,
+    pub balance: String,
+    pub height: String,
+    pub local_timestamp: String,
+    pub confirmed: String,
     pub subtype: String,
     pub contents: block::NanoBlock,
 }
@@ -95,15 +365,326 @@ pub struct RPCProcessReq {
     block: block::NanoBlock,
 }
 
-impl ClientRpc {
-    pub fn new(addr: &str) -> Result<ClientRpc, Box<dyn std::error::Error>> {
-        let client = Client::builder().build()?;
+#[derive(Serialize, Debug)]
+pub struct RPCAccountHistoryReq {
+    action: String,
+    account: String,
+    count: String,
+    // hash to start from, `previous` of the last page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    head: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RPCAccountHistoryResp {
+    pub account: String,
+    #[serde(deserialize_with = "empty_string_as_default")]
+    pub history: Vec<RPCHistoryEntry>,
+    // head of the next (older) page. missing on the last page.
+    pub previous: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RPCHistoryEntry {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub account: String,
+    pub amount: String,
+    pub local_timestamp: String,
+    pub height: String,
+    pub hash: String,
+    pub confirmed: Option<String>,
+}
+
+// accounts_balances, accounts_frontiers, accounts_pending and friends
+#[derive(Serialize, Debug)]
+pub struct RPCAccountsReq {
+    action: String,
+    accounts: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    count: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    threshold: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<bool>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RPCAccountsBalancesResp {
+    pub balances: HashMap<String, RPCAccountBalance>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RPCAccountBalance {
+    pub balance: String,
+    // older nodes only send pending, newer ones both
+    pub pending: Option<String>,
+    pub receivable: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RPCAccountsFrontiersResp {
+    #[serde(deserialize_with = "empty_string_as_default")]
+    pub frontiers: HashMap<String, String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RPCAccountsReceivableResp {
+    #[serde(deserialize_with = "empty_string_as_default")]
+    pub blocks: HashMap<String, RPCReceivableBlocks>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RPCPendingSource {
+    pub amount: String,
+    pub source: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RPCBlocksInfoReq {
+    action: String,
+    json_block: bool,
+    hashes: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RPCBlocksInfoResp {
+    pub blocks: HashMap<String, RPCBlockInfoResp>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RPCBlockCountResp {
+    pub count: String,
+    pub unchecked: String,
+    pub cemented: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RPCAccountRepresentativeResp {
+    pub representative: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RPCAccountWeightResp {
+    pub weight: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RPCRepresentativesOnlineResp {
+    #[serde(deserialize_with = "empty_string_as_default")]
+    pub representatives: HashMap<String, RPCRepresentativeWeight>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RPCRepresentativeWeight {
+    pub weight: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RPCConfirmationQuorumResp {
+    pub quorum_delta: String,
+    pub online_weight_quorum_percent: String,
+    pub online_weight_minimum: String,
+    pub online_stake_total: String,
+    pub peers_stake_total: String,
+    pub trended_stake_total: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RPCBlockConfirmResp {
+    pub started: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RPCVersionResp {
+    pub rpc_version: String,
+    pub store_version: String,
+    pub protocol_version: String,
+    pub node_vendor: String,
+    pub store_vendor: String,
+    pub network: String,
+    pub network_identifier: String,
+    pub build_info: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RPCPeersResp {
+    #[serde(deserialize_with = "empty_string_as_default")]
+    pub peers: HashMap<String, RPCPeer>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RPCPeer {
+    pub protocol_version: String,
+    pub node_id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+impl ClientRpcBuilder {
+    // endpoints are tried in the order they are added
+    pub fn endpoint(self, url: &str) -> ClientRpcBuilder {
+        self.endpoint_with(url, EndpointOptions::default())
+    }
+
+    pub fn endpoint_with(mut self, url: &str, options: EndpointOptions) -> ClientRpcBuilder {
+        self.endpoints.push((String::from(url), options));
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> ClientRpcBuilder {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> ClientRpcBuilder {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: &str) -> ClientRpcBuilder {
+        self.user_agent = String::from(user_agent);
+        self
+    }
+
+    pub fn build(self) -> Result<ClientRpc, Box<dyn std::error::Error>> {
+        if self.endpoints.is_empty() {
+            return Err("no rpc endpoints given".into());
+        }
+        let mut endpoints = vec![];
+        for (url, options) in &self.endpoints {
+            let client = self
+                .client(options)
+                .map_err(|e| format!("rpc endpoint {}: {}", url, e))?;
+            let mut endpoint = Endpoint::new(url, client);
+            if let Some(rate) = options.rate_limit {
+                if rate <= 0.0 {
+                    return Err(format!("rpc endpoint {}: rate_limit must be positive", url).into());
+                }
+                let burst = options.burst.map_or(rate.ceil().max(1.0), f64::from);
+                endpoint.bucket = Some(Mutex::new(TokenBucket::new(rate, burst)));
+            }
+            endpoint.permits = options.max_concurrent.map(Semaphore::new);
+            endpoints.push(endpoint);
+        }
         Ok(ClientRpc {
-            server_addr: String::from(addr),
-            client: Box::new(client),
+            endpoints: Arc::new(endpoints),
+            stats: Arc::new(Mutex::new(HashMap::new())),
         })
     }
 
+    fn client(&self, o: &EndpointOptions) -> Result<Client, Box<dyn std::error::Error>> {
+        let timeout = o
+            .timeout_secs
+            .map(Duration::from_secs)
+            .unwrap_or(self.timeout);
+        let user_agent = o.user_agent.as_deref().unwrap_or(&self.user_agent);
+        let mut headers = HeaderMap::new();
+        if let Some(token) = &o.bearer_token {
+            let mut v = HeaderValue::from_str(&format!("Bearer {}", token))?;
+            v.set_sensitive(true);
+            headers.insert(AUTHORIZATION, v);
+        }
+        for (k, v) in &o.headers {
+            let mut v = HeaderValue::from_str(v)?;
+            v.set_sensitive(true);
+            headers.insert(HeaderName::from_bytes(k.as_bytes())?, v);
+        }
+        let mut builder = Client::builder()
+            .timeout(timeout)
+            .user_agent(user_agent)
+            .default_headers(headers);
+        let connect_timeout = o.connect_timeout_secs.map(Duration::from_secs);
+        if let Some(t) = connect_timeout.or(self.connect_timeout) {
+            builder = builder.connect_timeout(t);
+        }
+        if let Some(proxy) = &o.proxy {
+            builder = builder.proxy(Proxy::all(proxy)?);
+        }
+        if let Some(path) = &o.ca_cert {
+            let pem = std::fs::read(path).map_err(|e| format!("ca_cert {}: {}", path, e))?;
+            builder = builder.add_root_certificate(Certificate::from_pem(&pem)?);
+        }
+        if o.pin_ca_cert {
+            if o.ca_cert.is_none() {
+                return Err("pin_ca_cert needs a ca_cert".into());
+            }
+            builder = builder.tls_built_in_root_certs(false);
+        }
+        Ok(builder.build()?)
+    }
+}
+
+impl ClientRpc {
+    pub fn builder() -> ClientRpcBuilder {
+        ClientRpcBuilder {
+            endpoints: vec![],
+            timeout: DEFAULT_TIMEOUT,
+            connect_timeout: None,
+            user_agent: String::from(DEFAULT_USER_AGENT),
+        }
+    }
+
+    pub fn new(addr: &str) -> Result<ClientRpc, Box<dyn std::error::Error>> {
+        ClientRpc::builder().endpoint(addr).build()
+    }
+
+    pub fn with_timeout(
+        addr: &str,
+        timeout: Duration,
+    ) -> Result<ClientRpc, Box<dyn std::error::Error>> {
+        ClientRpc::with_endpoints(&[addr], timeout)
+    }
+
+    // `addrs` in order of preference
+    pub fn with_endpoints<S: AsRef<str>>(
+        addrs: &[S],
+        timeout: Duration,
+    ) -> Result<ClientRpc, Box<dyn std::error::Error>> {
+        addrs
+            .iter()
+            .fold(ClientRpc::builder().timeout(timeout), |b, a| {
+                b.endpoint(a.as_ref())
+            })
+            .build()
+    }
+
+    pub fn health(&self) -> Vec<EndpointHealth> {
+        self.endpoints.iter().map(|e| e.health()).collect()
+    }
+
+    pub fn stats(&self) -> HashMap<String, ActionStats> {
+        self.stats.lock().unwrap().clone()
+    }
+
+    // asks every endpoint for telemetry to refresh latency and block counts
+    // and node versions. nodes that do not share telemetry are asked for `version`.
+    pub async fn check_health(&self) -> Vec<EndpointHealth> {
+        let r = action("telemetry", &[]);
+        for e in self.endpoints.iter() {
+            let major = match self.post_to::<RPCTelemetryResp, _>(e, &r).await {
+                Ok(Some(t)) => {
+                    e.health.lock().unwrap().block_count = t.block_count.parse().ok();
+                    t.major_version.parse().ok()
+                }
+                Err(PostError::Unreachable(_)) => continue,
+                _ => match self
+                    .post_to::<RPCVersionResp, _>(e, &action("version", &[]))
+                    .await
+                {
+                    Ok(Some(v)) => v.major_version(),
+                    _ => None,
+                },
+            };
+            if let Some(major) = major {
+                let mut health = e.health.lock().unwrap();
+                health.major_version = Some(major);
+                health.receivable = Some(major >= RECEIVABLE_MAJOR_VERSION);
+            }
+        }
+        self.health()
+    }
+
     pub async fn connect(
         &self,
     ) -> std::result::Result<RPCTelemetryResp, Box<dyn std::error::Error>> {
@@ -126,49 +707,87 @@ impl ClientRpc {
         }
     }
 
-    pub async fn block_info(&self, hash: &str) -> Option<RPCBlockInfoResp> {
+    pub async fn block_info(
+        &self,
+        hash: &str,
+    ) -> Result<Option<RPCBlockInfoResp>, Box<dyn std::error::Error>> {
         let r = HashMap::<_, _>::from_iter(IntoIter::new([
             ("action", "block_info"),
             ("json_block", "true"),
             ("hash", hash),
         ]));
-        match self
-            .rpc_post::<RPCBlockInfoResp, HashMap<&str, &str>>(r)
+        self.rpc_post::<RPCBlockInfoResp, HashMap<&str, &str>>(r)
             .await
-        {
-            Err(e) => {
-                eprintln!("\nrpc block info failed.\n error: {:#?}", e);
-                None
-            }
-            Ok(v) => {
-                //println!("{:?}", v);
-                v
-            }
-        }
     }
 
-    pub async fn account_info(&self, acct: &str) -> Option<RPCAccountInfoResp> {
+    // https://docs.nano.org/commands/rpc-protocol/#block_info
+    // asks every endpoint. confirmation status is part of the answer, so a node that
+    // has not confirmed the block yet counts as disagreeing.
+    pub async fn block_info_quorum(
+        &self,
+        hash: &str,
+        quorum: usize,
+    ) -> Result<QuorumResp<RPCBlockInfoResp>, String> {
+        let r = HashMap::<_, _>::from_iter(IntoIter::new([
+            ("action", "block_info"),
+            ("json_block", "true"),
+            ("hash", hash),
+        ]));
+        self.quorum(&r, quorum, |b: &RPCBlockInfoResp| {
+            (
+                b.block_account.clone(),
+                b.amount.clone(),
+                b.balance.clone(),
+                b.height.clone(),
+                b.confirmed.clone(),
+            )
+        })
+        .await
+    }
+
+    // https://docs.nano.org/commands/rpc-protocol/#account_info
+    pub async fn account_info_quorum(
+        &self,
+        acct: &str,
+        quorum: usize,
+    ) -> Result<QuorumResp<RPCAccountInfoResp>, String> {
         let r = HashMap::<_, _>::from_iter(IntoIter::new([
             ("action", "account_info"),
             ("representative", "true"),
             ("account", acct),
         ]));
-        match self
-            .rpc_post::<RPCAccountInfoResp, HashMap<&str, &str>>(r)
-            .await
-        {
-            Err(e) => {
-                eprintln!("\nrpc block info failed.\n error: {:#?}", e);
-                None
-            }
-            Ok(v) => {
-                //println!("account info: {:?}", v);
-                v
-            }
+        self.quorum(&r, quorum, |a: &RPCAccountInfoResp| {
+            (
+                a.frontier.clone(),
+                a.balance.clone(),
+                a.representative.clone(),
+            )
+        })
+        .await
+    }
+
+    // None when the account has not been opened yet
+    pub async fn account_info(
+        &self,
+        acct: &str,
+    ) -> Result<Option<RPCAccountInfoResp>, Box<dyn std::error::Error>> {
+        let r = HashMap::<_, _>::from_iter(IntoIter::new([
+            ("action", "account_info"),
+            ("representative", "true"),
+            ("account", acct),
+        ]));
+        match self.post::<RPCAccountInfoResp, _>(&r).await {
+            Err(PostError::Rejected(NodeError::AccountNotFound)) => Ok(None),
+            resp => resp.map_err(PostError::into_error),
         }
     }
 
-    pub async fn process(&self, block: &block::NanoBlock) -> Option<RPCProcessResp> {
+    // a failed publish may still have reached the node, so before trying the next
+    // endpoint we check whether the block is already known and stop if it is.
+    pub async fn process(
+        &self,
+        block: &block::NanoBlock,
+    ) -> Result<RPCProcessResp, Box<dyn std::error::Error>> {
         let subtype = block.subtype.expect("block to process missing subtype");
         let r = RPCProcessReq {
             action: String::from("process"),
@@ -177,53 +796,249 @@ impl ClientRpc {
             block: block.clone(),
         };
         //println!("{:#?}", r);
-        match self.rpc_post::<RPCProcessResp, RPCProcessReq>(r).await {
-            Err(e) => {
-                eprintln!("\nrpc process failed.\n error: {:#?}", e);
-                None
+        let mut last_err = String::from("no rpc endpoints");
+        for (i, e) in self.ordered().into_iter().enumerate() {
+            if i > 0 {
+                tokio::time::sleep(backoff(i)).await;
+                let hash = block
+                    .hash
+                    .as_deref()
+                    .ok_or("block to process missing hash")?;
+                match self.block_known(hash).await {
+                    Ok(true) => {
+                        return Ok(RPCProcessResp {
+                            hash: String::from(hash),
+                        })
+                    }
+                    Ok(false) => {}
+                    Err(err) => {
+                        return Err(format!(
+                            "process not retried, block state unknown. {}: {}",
+                            last_err, err
+                        )
+                        .into())
+                    }
+                }
             }
-            Ok(v) => {
-                //println!("processed: {:?}", v);
-                v
+            match self.post_to::<RPCProcessResp, _>(e, &r).await {
+                Ok(v) => return v.ok_or_else(|| "empty response".into()),
+                Err(PostError::Rejected(err)) => return Err(err.into()),
+                Err(PostError::Unreachable(err)) => last_err = format!("{}: {}", e.url, err),
             }
         }
+        Err(last_err.into())
+    }
+
+    // Err when no endpoint could answer
+    async fn block_known(&self, hash: &str) -> Result<bool, String> {
+        let r =
+            HashMap::<_, _>::from_iter(IntoIter::new([("action", "block_info"), ("hash", hash)]));
+        match self.post::<serde_json::Value, _>(&r).await {
+            Ok(_) => Ok(true),
+            Err(PostError::Rejected(NodeError::BlockNotFound)) => Ok(false),
+            Err(PostError::Rejected(e)) => Err(e.to_string()),
+            Err(PostError::Unreachable(e)) => Err(e),
+        }
     }
 
     // https://docs.nano.org/commands/rpc-protocol/#pending
-    pub async fn pending(&self, addr: &str) -> Option<RPCPendingResp> {
+    // https://docs.nano.org/commands/rpc-protocol/#receivable
+    // falls back to `pending` on nodes older than v23
+    pub async fn receivable(
+        &self,
+        addr: &str,
+    ) -> Result<Option<RPCReceivableResp>, Box<dyn std::error::Error>> {
+        let r = |action: &'static str| {
+            HashMap::<_, _>::from_iter(IntoIter::new([
+                ("action", action),
+                ("account", addr),
+                ("include_active", "true"),
+            ]))
+        };
+        self.post_receivable(r("receivable"), r("pending"))
+            .await
+            .map_err(PostError::into_error)
+    }
+
+    // https://docs.nano.org/commands/rpc-protocol/#work_generate
+    pub async fn work_generate(
+        &self,
+        hash: &str,
+        difficulty: &str,
+    ) -> Result<Option<RPCWorkGenResp>, Box<dyn std::error::Error>> {
         let r = HashMap::<_, _>::from_iter(IntoIter::new([
-            ("action", "pending"),
-            ("account", addr),
-            ("include_active", "true"),
+            ("action", "work_generate"),
+            ("hash", hash),
+            ("difficulty", difficulty),
         ]));
-        match self
-            .rpc_post::<RPCPendingResp, HashMap<&str, &str>>(r)
+        self.rpc_post::<RPCWorkGenResp, HashMap<&str, &str>>(r)
             .await
-        {
-            Err(e) => {
-                eprintln!("\nrpc pending failed.\n error: {:#?}", e);
-                None
-            }
-            Ok(v) => v,
-        }
     }
 
-    // https://docs.nano.org/commands/rpc-protocol/#pending
-    pub async fn work_generate(&self, hash: &str) -> Option<RPCWorkGenResp> {
+    // asks each endpoint in turn until `accept` takes its work. `accept` sees every
+    // answer, failures included, so the caller can report the ones passed over.
+    pub async fn work_generate_until<F>(
+        &self,
+        hash: &str,
+        difficulty: &str,
+        mut accept: F,
+    ) -> Option<RPCWorkGenResp>
+    where
+        F: FnMut(&str, Result<&RPCWorkGenResp, String>) -> bool,
+    {
         let r = HashMap::<_, _>::from_iter(IntoIter::new([
             ("action", "work_generate"),
             ("hash", hash),
+            ("difficulty", difficulty),
         ]));
-        match self
-            .rpc_post::<RPCWorkGenResp, HashMap<&str, &str>>(r)
-            .await
-        {
-            Err(e) => {
-                eprintln!("\nwork gen failed.\n error: {:#?}", e);
-                None
+        for e in self.ordered() {
+            let resp = match self.post_to::<RPCWorkGenResp, _>(e, &r).await {
+                Ok(Some(w)) => Ok(w),
+                Ok(None) => Err(String::from("empty response")),
+                Err(PostError::Rejected(err)) => Err(err.to_string()),
+                Err(PostError::Unreachable(err)) => Err(err),
+            };
+            match resp {
+                Ok(w) if accept(&e.url, Ok(&w)) => return Some(w),
+                Ok(_) => {}
+                Err(err) => {
+                    accept(&e.url, Err(err));
+                }
             }
-            Ok(v) => v,
         }
+        None
+    }
+
+    // https://docs.nano.org/commands/rpc-protocol/#account_history
+    // newest first. pass the `previous` of a page as `head` to get the next one.
+    pub async fn account_history(
+        &self,
+        acct: &str,
+        count: u64,
+        head: Option<&str>,
+    ) -> Result<RPCAccountHistoryResp, Box<dyn std::error::Error>> {
+        let r = RPCAccountHistoryReq {
+            action: String::from("account_history"),
+            account: String::from(acct),
+            count: count.to_string(),
+            head: head.map(String::from),
+        };
+        self.call(r).await
+    }
+
+    // https://docs.nano.org/commands/rpc-protocol/#accounts_balances
+    pub async fn accounts_balances(
+        &self,
+        accounts: &[String],
+    ) -> Result<RPCAccountsBalancesResp, Box<dyn std::error::Error>> {
+        self.call(RPCAccountsReq::new("accounts_balances", accounts))
+            .await
+    }
+
+    // https://docs.nano.org/commands/rpc-protocol/#accounts_frontiers
+    pub async fn accounts_frontiers(
+        &self,
+        accounts: &[String],
+    ) -> Result<RPCAccountsFrontiersResp, Box<dyn std::error::Error>> {
+        self.call(RPCAccountsReq::new("accounts_frontiers", accounts))
+            .await
+    }
+
+    // https://docs.nano.org/commands/rpc-protocol/#accounts_receivable
+    // up to `count` receivable blocks per account, at least `threshold` raw, with their
+    // source. falls back to `accounts_pending` on nodes older than v23.
+    pub async fn accounts_receivable(
+        &self,
+        accounts: &[String],
+        count: u64,
+        threshold: Option<&str>,
+    ) -> Result<RPCAccountsReceivableResp, Box<dyn std::error::Error>> {
+        let r = |action| RPCAccountsReq::new(action, accounts).receivable(count, threshold);
+        self.post_receivable(r("accounts_receivable"), r("accounts_pending"))
+            .await
+            .map_err(PostError::into_error)?
+            .ok_or_else(|| "empty response".into())
+    }
+
+    // https://docs.nano.org/commands/rpc-protocol/#blocks_info
+    pub async fn blocks_info(
+        &self,
+        hashes: &[String],
+    ) -> Result<RPCBlocksInfoResp, Box<dyn std::error::Error>> {
+        let r = RPCBlocksInfoReq {
+            action: String::from("blocks_info"),
+            json_block: true,
+            hashes: hashes.to_vec(),
+        };
+        self.call(r).await
+    }
+
+    // https://docs.nano.org/commands/rpc-protocol/#block_count
+    pub async fn block_count(&self) -> Result<RPCBlockCountResp, Box<dyn std::error::Error>> {
+        self.call(action("block_count", &[])).await
+    }
+
+    // https://docs.nano.org/commands/rpc-protocol/#account_representative
+    pub async fn account_representative(
+        &self,
+        acct: &str,
+    ) -> Result<RPCAccountRepresentativeResp, Box<dyn std::error::Error>> {
+        self.call(action("account_representative", &[("account", acct)]))
+            .await
+    }
+
+    // https://docs.nano.org/commands/rpc-protocol/#account_weight
+    pub async fn account_weight(
+        &self,
+        acct: &str,
+    ) -> Result<RPCAccountWeightResp, Box<dyn std::error::Error>> {
+        self.call(action("account_weight", &[("account", acct)]))
+            .await
+    }
+
+    // https://docs.nano.org/commands/rpc-protocol/#representatives_online
+    pub async fn representatives_online(
+        &self,
+    ) -> Result<RPCRepresentativesOnlineResp, Box<dyn std::error::Error>> {
+        self.call(action("representatives_online", &[("weight", "true")]))
+            .await
+    }
+
+    // https://docs.nano.org/commands/rpc-protocol/#confirmation_quorum
+    pub async fn confirmation_quorum(
+        &self,
+    ) -> Result<RPCConfirmationQuorumResp, Box<dyn std::error::Error>> {
+        self.call(action("confirmation_quorum", &[])).await
+    }
+
+    // https://docs.nano.org/commands/rpc-protocol/#block_confirm
+    // asks the node to start an election for the block. the result arrives over websocket.
+    pub async fn block_confirm(
+        &self,
+        hash: &str,
+    ) -> Result<RPCBlockConfirmResp, Box<dyn std::error::Error>> {
+        self.call(action("block_confirm", &[("hash", hash)])).await
+    }
+
+    // https://docs.nano.org/commands/rpc-protocol/#version
+    pub async fn version(&self) -> Result<RPCVersionResp, Box<dyn std::error::Error>> {
+        self.call(action("version", &[])).await
+    }
+
+    // https://docs.nano.org/commands/rpc-protocol/#peers
+    pub async fn peers(&self) -> Result<RPCPeersResp, Box<dyn std::error::Error>> {
+        self.call(action("peers", &[("peer_details", "true")]))
+            .await
+    }
+
+    async fn call<T, P>(&self, r: P) -> Result<T, Box<dyn std::error::Error>>
+    where
+        T: DeserializeOwned,
+        P: Serialize,
+    {
+        self.rpc_post(r)
+            .await?
+            .ok_or_else(|| "empty response".into())
     }
 
     async fn rpc_post<T, P>(&self, r: P) -> Result<Option<T>, Box<dyn std::error::Error>>
@@ -231,20 +1046,889 @@ impl ClientRpc {
         T: DeserializeOwned,
         P: Serialize,
     {
-        let resp = self.client.post(&self.server_addr).json(&r).send().await?;
+        self.post(&r).await.map_err(PostError::into_error)
+    }
+
+    // only for idempotent actions: on failure the request is sent to the next endpoint
+    async fn post<T, P>(&self, r: &P) -> Result<Option<T>, PostError>
+    where
+        T: DeserializeOwned,
+        P: Serialize,
+    {
+        let mut last_err = String::new();
+        for (i, e) in self.ordered().into_iter().enumerate() {
+            if i > 0 {
+                tokio::time::sleep(backoff(i)).await;
+            }
+            match self.post_to(e, r).await {
+                Err(PostError::Unreachable(err)) => last_err = format!("{}: {}", e.url, err),
+                resp => return resp,
+            }
+        }
+        Err(PostError::Unreachable(last_err))
+    }
+
+    // like `post`, but sends `legacy` to nodes that do not know the receivable actions.
+    // nodes we have no version for get `current` first and are remembered if they reject it.
+    async fn post_receivable<T, P>(&self, current: P, legacy: P) -> Result<Option<T>, PostError>
+    where
+        T: DeserializeOwned,
+        P: Serialize,
+    {
+        let mut last_err = String::new();
+        for (i, e) in self.ordered().into_iter().enumerate() {
+            if i > 0 {
+                tokio::time::sleep(backoff(i)).await;
+            }
+            let known = e.health().receivable;
+            let resp = match known {
+                Some(false) => self.post_to(e, &legacy).await,
+                _ => match self.post_to(e, &current).await {
+                    Err(PostError::Rejected(NodeError::UnknownCommand)) if known.is_none() => {
+                        e.health.lock().unwrap().receivable = Some(false);
+                        self.post_to(e, &legacy).await
+                    }
+                    resp => resp,
+                },
+            };
+            match resp {
+                Err(PostError::Unreachable(err)) => last_err = format!("{}: {}", e.url, err),
+                resp => return resp,
+            }
+        }
+        Err(PostError::Unreachable(last_err))
+    }
+
+    async fn post_to<T, P>(&self, e: &Endpoint, r: &P) -> Result<Option<T>, PostError>
+    where
+        T: DeserializeOwned,
+        P: Serialize,
+    {
+        let _permit = match &e.permits {
+            Some(p) => Some(
+                p.acquire()
+                    .await
+                    .map_err(|e| PostError::Unreachable(e.to_string()))?,
+            ),
+            None => None,
+        };
+        e.wait_turn().await;
+        let start = Instant::now();
+        let resp = self.send(e, r).await;
+        let name = serde_json::to_value(r)
+            .ok()
+            .and_then(|v| v["action"].as_str().map(String::from))
+            .unwrap_or_default();
+        let mut stats = self.stats.lock().unwrap();
+        let stats = stats.entry(name).or_default();
+        stats.requests += 1;
+        let mut health = e.health.lock().unwrap();
+        match resp {
+            Err(throttled) => {
+                stats.rate_limited += 1;
+                health.rate_limited += 1;
+                let wait = throttled
+                    .retry_after
+                    .unwrap_or_else(|| THROTTLE_BACKOFF * 2u32.pow(health.rate_limited.min(5) - 1))
+                    .min(MAX_THROTTLE_BACKOFF);
+                health.throttled_until = Some(Instant::now() + wait);
+                Err(PostError::Unreachable(format!(
+                    "rate limited: {}",
+                    throttled.msg
+                )))
+            }
+            Ok(Err(PostError::Unreachable(err))) => {
+                stats.failed += 1;
+                health.consecutive_failures += 1;
+                Err(PostError::Unreachable(err))
+            }
+            Ok(resp) => {
+                health.consecutive_failures = 0;
+                health.rate_limited = 0;
+                health.latency = Some(start.elapsed());
+                resp
+            }
+        }
+    }
+
+    // Err when the endpoint rate limits us, by http status or in an error body
+    async fn send<T, P>(
+        &self,
+        e: &Endpoint,
+        r: &P,
+    ) -> Result<Result<Option<T>, PostError>, Throttled>
+    where
+        T: DeserializeOwned,
+        P: Serialize,
+    {
+        let unreachable = |err: reqwest::Error| Ok(Err(PostError::Unreachable(err.to_string())));
+        let resp = match e.client.post(&e.url).json(r).send().await {
+            Ok(resp) => resp,
+            Err(err) => return unreachable(err),
+        };
         let status = resp.status();
-        let resp = resp.text().await?;
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = resp
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_secs);
+            return Err(Throttled {
+                retry_after,
+                msg: format!("received {} from node", status),
+            });
+        }
+        let resp = match resp.text().await {
+            Ok(resp) => resp,
+            Err(err) => return unreachable(err),
+        };
         if status.is_client_error() || status.is_server_error() {
-            return Err(format!("received {} from node. error: {}", status, resp).into());
+            return Ok(Err(PostError::Unreachable(format!(
+                "received {} from node. error: {}",
+                status, resp
+            ))));
         }
         //println!("\nstatus: {}, body: {}\n", status, resp);
-        let resp = match serde_json::from_str(&resp) {
-            Ok(t) => Ok(Some(t)),
-            Err(e) => {
-                //eprintln!("{:?}", e);
-                Err(format!("response serialization error: {:?}", e).into())
+        // nodes answer errors with 200 too, and the envelope could pass for a response
+        // whose fields are all optional, so it is checked first
+        if let Ok(err) = serde_json::from_str::<RPCErrorResp>(&resp) {
+            if rate_limit_error(&err.error) {
+                return Err(Throttled {
+                    retry_after: None,
+                    msg: err.error,
+                });
             }
+            return Ok(Err(PostError::Rejected(NodeError::from_message(
+                &err.error,
+            ))));
+        }
+        match serde_json::from_str(&resp) {
+            Ok(t) => Ok(Ok(Some(t))),
+            Err(e) => Ok(Err(PostError::Unreachable(format!(
+                "response serialization error: {:?}",
+                e
+            )))),
+        }
+    }
+
+    // sends `r` to every endpoint at once. answers are grouped by `key`, and the largest
+    // group wins if at least `quorum` endpoints answered and it is a strict majority of them.
+    async fn quorum<T, P, K, F>(
+        &self,
+        r: &P,
+        quorum: usize,
+        key: F,
+    ) -> Result<QuorumResp<T>, String>
+    where
+        T: DeserializeOwned,
+        P: Serialize,
+        K: PartialEq,
+        F: Fn(&T) -> K,
+    {
+        let answers = futures::future::join_all(self.endpoints.iter().map(|e| async move {
+            let answer = match self.post_to::<T, _>(e, r).await {
+                Ok(Some(t)) => Ok(t),
+                Ok(None) => return None,
+                Err(PostError::Rejected(err)) => Err(err),
+                Err(PostError::Unreachable(_)) => return None,
+            };
+            Some((e.url.clone(), answer))
+        }))
+        .await;
+        let answers: Vec<(String, Result<T, NodeError>)> = answers.into_iter().flatten().collect();
+        let responded = answers.len();
+        if responded < quorum {
+            return Err(format!(
+                "only {} of {} nodes answered, {} needed",
+                responded,
+                self.endpoints.len(),
+                quorum
+            ));
+        }
+        let keys: Vec<Result<K, &NodeError>> =
+            answers.iter().map(|(_, a)| a.as_ref().map(&key)).collect();
+        let agreeing = |i: usize| keys.iter().filter(|k| **k == keys[i]).count();
+        let best = (0..responded).max_by_key(|i| agreeing(*i)).unwrap();
+        let count = agreeing(best);
+        if count * 2 <= responded {
+            return Err(format!(
+                "nodes disagree, largest group is {} of {}",
+                count, responded
+            ));
+        }
+        let dissent = (0..responded)
+            .filter(|i| keys[*i] != keys[best])
+            .map(|i| answers[i].0.clone())
+            .collect();
+        drop(keys);
+        let value = answers.into_iter().nth(best).unwrap().1;
+        Ok(QuorumResp {
+            value,
+            agreeing: count,
+            responded,
+            dissent,
+        })
+    }
+
+    // healthy endpoints in priority order, then the rest as a last resort
+    fn ordered(&self) -> Vec<&Endpoint> {
+        let health = self.health();
+        let best = health.iter().filter_map(|h| h.block_count).max();
+        let (mut healthy, unhealthy): (Vec<_>, Vec<_>) = self
+            .endpoints
+            .iter()
+            .zip(&health)
+            .partition(|(_, h)| h.is_healthy(best));
+        healthy.extend(unhealthy);
+        healthy.into_iter().map(|(e, _)| e).collect()
+    }
+}
+
+impl Endpoint {
+    fn new(url: &str, client: Client) -> Endpoint {
+        Endpoint {
+            url: String::from(url),
+            client,
+            health: Mutex::new(EndpointHealth {
+                url: String::from(url),
+                latency: None,
+                consecutive_failures: 0,
+                block_count: None,
+                major_version: None,
+                receivable: None,
+                rate_limited: 0,
+                throttled_until: None,
+            }),
+            bucket: None,
+            permits: None,
+        }
+    }
+
+    fn health(&self) -> EndpointHealth {
+        self.health.lock().unwrap().clone()
+    }
+
+    // waits out a rate limit backoff, then for a token from the bucket
+    async fn wait_turn(&self) {
+        loop {
+            let now = Instant::now();
+            let wait = match self.health.lock().unwrap().throttled_until {
+                Some(until) if until > now => Some(until - now),
+                _ => self
+                    .bucket
+                    .as_ref()
+                    .and_then(|b| b.lock().unwrap().take(now)),
+            };
+            match wait {
+                Some(wait) => tokio::time::sleep(wait).await,
+                None => return,
+            }
+        }
+    }
+}
+
+impl TokenBucket {
+    fn new(rate: f64, burst: f64) -> TokenBucket {
+        TokenBucket {
+            rate,
+            burst,
+            tokens: burst,
+            last: Instant::now(),
+        }
+    }
+
+    // takes a token, or says how long until there is one
+    fn take(&mut self, now: Instant) -> Option<Duration> {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+        self.last = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            None
+        } else {
+            Some(Duration::from_secs_f64((1.0 - self.tokens) / self.rate))
+        }
+    }
+}
+
+impl RPCAccountsReq {
+    fn new(action: &str, accounts: &[String]) -> RPCAccountsReq {
+        RPCAccountsReq {
+            action: String::from(action),
+            accounts: accounts.to_vec(),
+            count: None,
+            threshold: None,
+            source: None,
+        }
+    }
+
+    fn receivable(self, count: u64, threshold: Option<&str>) -> RPCAccountsReq {
+        RPCAccountsReq {
+            count: Some(count.to_string()),
+            threshold: threshold.map(String::from),
+            source: Some(true),
+            ..self
+        }
+    }
+}
+
+fn action<'a>(action: &'a str, params: &[(&'a str, &'a str)]) -> HashMap<&'a str, &'a str> {
+    let mut r = HashMap::<_, _>::from_iter(params.iter().cloned());
+    r.insert("action", action);
+    r
+}
+
+impl RPCVersionResp {
+    // node_vendor looks like "Nano V23.0"
+    pub fn major_version(&self) -> Option<u32> {
+        let v = self.node_vendor.rsplit(' ').next()?;
+        v.trim_start_matches('V').split('.').next()?.parse().ok()
+    }
+}
+
+// public nodes and proxies word this differently
+fn rate_limit_error(err: &str) -> bool {
+    let err = err.to_lowercase();
+    err.contains("rate limit") || err.contains("too many requests")
+}
+
+fn backoff(attempt: usize) -> Duration {
+    RETRY_BACKOFF * 2u32.pow(attempt.saturating_sub(1) as u32)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+
+    use super::*;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Response, Server};
+    use std::convert::Infallible;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // serves `handler(action)` and counts the requests it gets
+    pub(crate) async fn fixture_node(
+        handler: fn(&str) -> (u16, String),
+    ) -> (String, Arc<AtomicUsize>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let make_svc = make_service_fn(move |_| {
+            let counter = counter.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let counter = counter.clone();
+                    async move {
+                        counter.fetch_add(1, Ordering::SeqCst);
+                        let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+                        let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                        let (status, body) = handler(v["action"].as_str().unwrap());
+                        Ok::<_, Infallible>(
+                            Response::builder()
+                                .status(status)
+                                .body(Body::from(body))
+                                .unwrap(),
+                        )
+                    }
+                }))
+            }
+        });
+        tokio::spawn(Server::from_tcp(listener).unwrap().serve(make_svc));
+        (format!("http://{}", addr), hits)
+    }
+
+    fn telemetry(count: &str) -> String {
+        format!(
+            r#"{{"block_count": "{}", "peer_count": "1", "major_version": "23",
+            "minor_version": "0", "patch_version": "0", "active_difficulty": "fffffff800000000"}}"#,
+            count
+        )
+    }
+
+    fn test_block() -> block::NanoBlock {
+        let mut b: block::NanoBlock = serde_json::from_str(
+            r#"{"type": "state", "account": "nano_1e69ju7uc6eu3zkgm3krmu9x7hejdnx8sgkaah3ywo5xws6ttcy1g4yeo4bi",
+            "previous": "0", "representative": "nano_1e69ju7uc6eu3zkgm3krmu9x7hejdnx8sgkaah3ywo5xws6ttcy1g4yeo4bi",
+            "balance": "0", "link": "0", "work": "0", "subtype": "send"}"#,
+        )
+        .unwrap();
+        b.hash = Some("AB".repeat(32));
+        b
+    }
+
+    fn account_info(balance: &str) -> (u16, String) {
+        let body = format!(
+            r#"{{"frontier": "{}", "open_block": "{}", "representative_block": "{}",
+            "representative": "nano_1e69ju7uc6eu3zkgm3krmu9x7hejdnx8sgkaah3ywo5xws6ttcy1g4yeo4bi",
+            "balance": "{}", "modified_timestamp": "0", "block_count": "1", "account_version": "2",
+            "confirmation_height": "1", "confirmation_height_frontier": "{}"}}"#,
+            "AB".repeat(32),
+            "AB".repeat(32),
+            "AB".repeat(32),
+            balance,
+            "AB".repeat(32)
+        );
+        (200, body)
+    }
+
+    // responses recorded from a node, keyed by action
+    fn recorded(action: &str) -> (u16, String) {
+        let body = match action {
+            "account_history" => include_str!("../tests/fixtures/rpc/account_history.json"),
+            "accounts_balances" => include_str!("../tests/fixtures/rpc/accounts_balances.json"),
+            "accounts_frontiers" => include_str!("../tests/fixtures/rpc/accounts_frontiers.json"),
+            "accounts_receivable" => include_str!("../tests/fixtures/rpc/accounts_receivable.json"),
+            "blocks_info" => include_str!("../tests/fixtures/rpc/blocks_info.json"),
+            "block_count" => include_str!("../tests/fixtures/rpc/block_count.json"),
+            "account_representative" => {
+                include_str!("../tests/fixtures/rpc/account_representative.json")
+            }
+            "account_weight" => include_str!("../tests/fixtures/rpc/account_weight.json"),
+            "representatives_online" => {
+                include_str!("../tests/fixtures/rpc/representatives_online.json")
+            }
+            "confirmation_quorum" => include_str!("../tests/fixtures/rpc/confirmation_quorum.json"),
+            "block_confirm" => include_str!("../tests/fixtures/rpc/block_confirm.json"),
+            "version" => include_str!("../tests/fixtures/rpc/version.json"),
+            "peers" => include_str!("../tests/fixtures/rpc/peers.json"),
+            _ => r#"{"error": "Unknown command"}"#,
+        };
+        (200, String::from(body))
+    }
+
+    const GENESIS: &str = "nano_3t6k35gi95xu6tergt6p69ck76ogmitsa8mnijtpxm9fkcm736xtoncuohr3";
+
+    #[tokio::test]
+    async fn reads_account_history_pages() {
+        let (url, _) = fixture_node(recorded).await;
+        let rpc = ClientRpc::new(&url).unwrap();
+        let page = rpc.account_history(GENESIS, 2, None).await.unwrap();
+        assert_eq!(page.history.len(), 2);
+        assert_eq!(page.history[0].kind, "send");
+        assert_eq!(page.history[1].height, "59");
+        assert_eq!(
+            page.previous.as_deref(),
+            Some("8D3AB98B301224253750D448B4BD997132400CEDD0A8432F775724F2D9821C72")
+        );
+        let last: RPCAccountHistoryResp =
+            serde_json::from_str(r#"{"account": "x", "history": ""}"#).unwrap();
+        assert!(last.history.is_empty() && last.previous.is_none());
+    }
+
+    #[tokio::test]
+    async fn reads_accounts_batches() {
+        let (url, _) = fixture_node(recorded).await;
+        let rpc = ClientRpc::new(&url).unwrap();
+        let accounts = vec![String::from(GENESIS)];
+        let balances = rpc.accounts_balances(&accounts).await.unwrap().balances;
+        assert_eq!(balances[GENESIS].balance, "325586539664609129644855132177");
+        assert!(balances[GENESIS].receivable.is_some());
+        let frontiers = rpc.accounts_frontiers(&accounts).await.unwrap().frontiers;
+        assert_eq!(
+            frontiers[GENESIS],
+            "791AF413173EEE674A6FCF633B5DFC0F3C33F397F0DA08E987D9E0741D40D81A"
+        );
+        let pending = rpc.accounts_receivable(&accounts, 10, None).await.unwrap();
+        assert_eq!(
+            pending.blocks[GENESIS].0,
+            vec![RPCReceivable {
+                hash: String::from(
+                    "4C1FEEF0BEA7F50BE35489A1233FE002B212DEA554B55B1B470D78BD8F210C74"
+                ),
+                amount: Some(String::from("106370018000000000000000000000000")),
+                source: Some(String::from(
+                    "nano_13ezf4od79h1tgj9aiu4djzcmmguendtjfuhwfukhuucboua8cpoihmh8byo"
+                )),
+            }]
+        );
+        let hash = "87434F8041869A01C8F6F263B87972D7BA443A72E0A97D7A3FD0CCC2358FD6F9";
+        let blocks = rpc.blocks_info(&[String::from(hash)]).await.unwrap().blocks;
+        assert_eq!(blocks[hash].height, "58");
+        assert_eq!(blocks[hash].contents.work, "8a142e07a10996d5");
+    }
+
+    #[tokio::test]
+    async fn reads_node_state() {
+        let (url, _) = fixture_node(recorded).await;
+        let rpc = ClientRpc::new(&url).unwrap();
+        assert_eq!(rpc.block_count().await.unwrap().cemented, "25");
+        assert_eq!(
+            rpc.account_representative(GENESIS)
+                .await
+                .unwrap()
+                .representative,
+            "nano_16u1uufyoig8777y6r8iqjtrw8sg8maqrm36zzcm95jmbd9i9aj5i8abr8u5"
+        );
+        assert_eq!(rpc.account_weight(GENESIS).await.unwrap().weight, "10000");
+        assert_eq!(
+            rpc.representatives_online()
+                .await
+                .unwrap()
+                .representatives
+                .len(),
+            2
+        );
+        assert_eq!(
+            rpc.confirmation_quorum()
+                .await
+                .unwrap()
+                .online_weight_quorum_percent,
+            "50"
+        );
+        assert_eq!(
+            rpc.block_confirm(&"0".repeat(64)).await.unwrap().started,
+            "1"
+        );
+        assert_eq!(rpc.version().await.unwrap().node_vendor, "Nano V23.0");
+        let peers = rpc.peers().await.unwrap().peers;
+        assert_eq!(peers["[::ffff:172.17.0.1]:32841"].kind, "tcp");
+    }
+
+    #[test]
+    fn builds_receivable_requests() {
+        let r = RPCAccountsReq::new("accounts_pending", &[String::from(GENESIS)])
+            .receivable(5, Some("1000"));
+        assert_eq!(
+            serde_json::to_value(&r).unwrap(),
+            serde_json::json!({
+                "action": "accounts_pending",
+                "accounts": [GENESIS],
+                "count": "5",
+                "threshold": "1000",
+                "source": true,
+            })
+        );
+        let b = serde_json::to_value(RPCAccountsReq::new("accounts_balances", &[])).unwrap();
+        assert_eq!(
+            b,
+            serde_json::json!({"action": "accounts_balances", "accounts": []})
+        );
+    }
+
+    #[test]
+    fn normalises_receivable_shapes() {
+        let blocks = |json: &str| {
+            let mut r = serde_json::from_str::<RPCReceivableResp>(json)
+                .unwrap()
+                .blocks
+                .0;
+            r.sort_by(|a, b| a.hash.cmp(&b.hash));
+            r
+        };
+        assert!(blocks(r#"{"blocks": ""}"#).is_empty());
+        assert!(blocks(r#"{"blocks": []}"#).is_empty());
+        assert!(blocks(r#"{"blocks": {}}"#).is_empty());
+        let hashes = blocks(r#"{"blocks": ["A1", "B2"]}"#);
+        assert_eq!(hashes[1].hash, "B2");
+        assert_eq!(hashes[1].amount, None);
+        let amounts = blocks(r#"{"blocks": {"A1": "10"}}"#);
+        assert_eq!(amounts[0].amount.as_deref(), Some("10"));
+        let sources = blocks(r#"{"blocks": {"A1": {"amount": "10", "source": "nano_x"}}}"#);
+        assert_eq!(sources[0].source.as_deref(), Some("nano_x"));
+        let accounts: RPCAccountsReceivableResp =
+            serde_json::from_str(r#"{"blocks": {"nano_a": "", "nano_b": ["A1"]}}"#).unwrap();
+        assert!(accounts.blocks["nano_a"].0.is_empty());
+        assert_eq!(accounts.blocks["nano_b"].0.len(), 1);
+        let none: RPCAccountsReceivableResp = serde_json::from_str(r#"{"blocks": ""}"#).unwrap();
+        assert!(none.blocks.is_empty());
+    }
+
+    // a v21 node: no receivable actions
+    fn legacy_node(action: &str) -> (u16, String) {
+        match action {
+            "receivable" | "accounts_receivable" => {
+                (200, String::from(r#"{"error": "Unknown command"}"#))
+            }
+            "pending" => (
+                200,
+                String::from(include_str!("../tests/fixtures/rpc/pending.json")),
+            ),
+            "telemetry" => (
+                200,
+                String::from(include_str!("../tests/fixtures/rpc/telemetry.json")),
+            ),
+            _ => recorded(action),
+        }
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_pending_on_old_nodes() {
+        let (url, hits) = fixture_node(legacy_node).await;
+        let rpc = ClientRpc::new(&url).unwrap();
+        let r = rpc.receivable(GENESIS).await.unwrap().unwrap();
+        assert_eq!(r.blocks.0.len(), 2);
+        assert_eq!(rpc.health()[0].receivable, Some(false));
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+        // now it goes straight to pending
+        rpc.receivable(GENESIS).await.unwrap().unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn detects_node_versions() {
+        let (old, _) = fixture_node(legacy_node).await;
+        // no telemetry here, so the version comes from `version`
+        let (new, _) = fixture_node(recorded).await;
+        let rpc = ClientRpc::with_endpoints(&[old, new], Duration::from_secs(5)).unwrap();
+        let health = rpc.check_health().await;
+        assert_eq!(health[0].major_version, Some(21));
+        assert_eq!(health[0].receivable, Some(false));
+        assert_eq!(health[0].block_count, Some(5777903));
+        assert_eq!(health[1].major_version, Some(23));
+        assert_eq!(health[1].receivable, Some(true));
+    }
+
+    // answers every request with the headers it came with
+    async fn echo_headers_node() -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let make_svc = make_service_fn(|_| async {
+            Ok::<_, Infallible>(service_fn(|req: hyper::Request<Body>| async move {
+                let headers: HashMap<String, String> = req
+                    .headers()
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_str().unwrap().to_string()))
+                    .collect();
+                let body = serde_json::to_string(&headers).unwrap();
+                Ok::<_, Infallible>(Response::new(Body::from(body)))
+            }))
+        });
+        tokio::spawn(Server::from_tcp(listener).unwrap().serve(make_svc));
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn sends_endpoint_headers() {
+        let url = echo_headers_node().await;
+        let mut options = EndpointOptions {
+            bearer_token: Some(String::from("token")),
+            ..Default::default()
+        };
+        options
+            .headers
+            .insert(String::from("X-Api-Key"), String::from("key"));
+        let rpc = ClientRpc::builder()
+            .endpoint_with(&url, options)
+            .user_agent("tests")
+            .build()
+            .unwrap();
+        let headers: HashMap<String, String> = rpc.call(action("version", &[])).await.unwrap();
+        assert_eq!(headers["authorization"], "Bearer token");
+        assert_eq!(headers["x-api-key"], "key");
+        assert_eq!(headers["user-agent"], "tests");
+        let plain = ClientRpc::new(&url).unwrap();
+        let headers: HashMap<String, String> = plain.call(action("version", &[])).await.unwrap();
+        assert!(!headers.contains_key("authorization"));
+        assert_eq!(headers["user-agent"], DEFAULT_USER_AGENT);
+    }
+
+    #[tokio::test]
+    async fn goes_through_proxy() {
+        let (proxy, hits) = fixture_node(recorded).await;
+        let options = EndpointOptions {
+            proxy: Some(proxy),
+            ..Default::default()
+        };
+        let rpc = ClientRpc::builder()
+            .endpoint_with("http://node.invalid:7076", options)
+            .build()
+            .unwrap();
+        assert_eq!(rpc.block_count().await.unwrap().count, "1000");
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn rejects_bad_endpoint_options() {
+        let build = |options| {
+            ClientRpc::builder()
+                .endpoint_with("http://a", options)
+                .build()
+        };
+        assert!(build(EndpointOptions {
+            pin_ca_cert: true,
+            ..Default::default()
+        })
+        .is_err());
+        assert!(build(EndpointOptions {
+            ca_cert: Some(String::from("/nonexistent/ca.pem")),
+            ..Default::default()
+        })
+        .is_err());
+        assert!(ClientRpc::builder().build().is_err());
+    }
+
+    #[test]
+    fn token_bucket_refills() {
+        let start = Instant::now();
+        let mut b = TokenBucket::new(10.0, 2.0);
+        assert_eq!(b.take(start), None);
+        assert_eq!(b.take(start), None);
+        let wait = b.take(start).unwrap();
+        assert!(wait > Duration::from_millis(90) && wait <= Duration::from_millis(100));
+        assert_eq!(b.take(start + Duration::from_millis(100)), None);
+    }
+
+    #[tokio::test]
+    async fn spaces_out_requests() {
+        let (url, _) = fixture_node(recorded).await;
+        let options = EndpointOptions {
+            rate_limit: Some(20.0),
+            burst: Some(1),
+            ..Default::default()
         };
-        resp
+        let rpc = ClientRpc::builder()
+            .endpoint_with(&url, options)
+            .build()
+            .unwrap();
+        let start = Instant::now();
+        for _ in 0..3 {
+            rpc.block_count().await.unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        assert_eq!(rpc.stats()["block_count"].requests, 3);
+    }
+
+    #[tokio::test]
+    async fn backs_off_rate_limited_endpoints() {
+        let (limited, limited_hits) = fixture_node(|_| (429, String::new())).await;
+        let (busy, _) =
+            fixture_node(|_| (200, String::from(r#"{"error": "Too many requests"}"#))).await;
+        let (ok, _) = fixture_node(recorded).await;
+        let rpc = ClientRpc::with_endpoints(&[limited, busy, ok], Duration::from_secs(5)).unwrap();
+        rpc.block_count().await.unwrap();
+        let health = rpc.health();
+        assert!(health[0].throttled_until.is_some() && health[1].throttled_until.is_some());
+        // throttled endpoints are not failures, but are skipped while backing off
+        assert_eq!(health[0].consecutive_failures, 0);
+        assert!(!health[0].is_healthy(None));
+        rpc.block_count().await.unwrap();
+        assert_eq!(limited_hits.load(Ordering::SeqCst), 1);
+        let stats = &rpc.stats()["block_count"];
+        assert_eq!(
+            (stats.requests, stats.rate_limited, stats.failed),
+            (4, 2, 0)
+        );
+    }
+
+    #[tokio::test]
+    async fn majority_wins_quorum() {
+        let (a, _) = fixture_node(|_| account_info("100")).await;
+        let (b, _) = fixture_node(|_| account_info("100")).await;
+        let (liar, _) = fixture_node(|_| account_info("999999")).await;
+        let rpc = ClientRpc::with_endpoints(&[a, b, liar.clone()], Duration::from_secs(5)).unwrap();
+        let resp = rpc.account_info_quorum(&"0".repeat(64), 2).await.unwrap();
+        assert_eq!(resp.value.unwrap().balance, "100");
+        assert_eq!((resp.agreeing, resp.responded), (2, 3));
+        assert_eq!(resp.dissent, vec![liar]);
+    }
+
+    #[tokio::test]
+    async fn quorum_fails_without_majority() {
+        let (a, _) = fixture_node(|_| account_info("100")).await;
+        let (b, _) = fixture_node(|_| account_info("200")).await;
+        let (down, _) = fixture_node(|_| (500, String::from("down"))).await;
+        let rpc = ClientRpc::with_endpoints(&[a, b, down], Duration::from_secs(5)).unwrap();
+        // two answered but they disagree
+        assert!(rpc.account_info_quorum(&"0".repeat(64), 2).await.is_err());
+        // not enough answers
+        assert!(rpc.account_info_quorum(&"0".repeat(64), 3).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn quorum_agrees_on_node_errors() {
+        let (a, _) =
+            fixture_node(|_| (200, String::from(r#"{"error": "Account not found"}"#))).await;
+        let (b, _) =
+            fixture_node(|_| (200, String::from(r#"{"error": "Account not found"}"#))).await;
+        let rpc = ClientRpc::with_endpoints(&[a, b], Duration::from_secs(5)).unwrap();
+        let resp = rpc.account_info_quorum(&"0".repeat(64), 2).await.unwrap();
+        assert_eq!(resp.value.unwrap_err(), NodeError::AccountNotFound);
+    }
+
+    #[tokio::test]
+    async fn fails_over_to_next_endpoint() {
+        let (down, down_hits) = fixture_node(|_| (500, String::from("down"))).await;
+        let (up, _) = fixture_node(|_| (200, telemetry("100"))).await;
+        let rpc = ClientRpc::with_endpoints(&[down, up], Duration::from_secs(5)).unwrap();
+        for _ in 0..MAX_CONSECUTIVE_FAILURES + 1 {
+            rpc.connect().await.unwrap();
+        }
+        // once the first endpoint is unhealthy it is no longer tried first
+        assert_eq!(
+            down_hits.load(Ordering::SeqCst),
+            MAX_CONSECUTIVE_FAILURES as usize
+        );
+        assert_eq!(
+            rpc.health()[0].consecutive_failures,
+            MAX_CONSECUTIVE_FAILURES
+        );
+    }
+
+    #[tokio::test]
+    async fn prefers_endpoints_that_keep_up() {
+        let (behind, _) = fixture_node(|_| (200, telemetry("10"))).await;
+        let (ahead, _) = fixture_node(|_| (200, telemetry("5000"))).await;
+        let rpc =
+            ClientRpc::with_endpoints(&[behind, ahead.clone()], Duration::from_secs(5)).unwrap();
+        let health = rpc.check_health().await;
+        assert!(!health[0].is_healthy(Some(5000)));
+        assert_eq!(rpc.ordered()[0].url, ahead);
+    }
+
+    #[tokio::test]
+    async fn does_not_republish_known_blocks() {
+        let (down, _) = fixture_node(|_| (502, String::from("bad gateway"))).await;
+        let (knows, hits) = fixture_node(|action| match action {
+            "block_info" => (200, String::from(r#"{"contents": {}}"#)),
+            _ => (200, String::from(r#"{"error": "Old block"}"#)),
+        })
+        .await;
+        let rpc = ClientRpc::with_endpoints(&[down, knows], Duration::from_secs(5)).unwrap();
+        let b = test_block();
+        let resp = rpc.process(&b).await.unwrap();
+        assert_eq!(Some(resp.hash), b.hash);
+        // only the block_info check, the block was not sent again
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn types_node_errors() {
+        assert_eq!(NodeError::from_message("Fork"), NodeError::Fork);
+        assert_eq!(
+            NodeError::from_message("Block work is less than threshold"),
+            NodeError::InsufficientWork
+        );
+        assert_eq!(
+            NodeError::from_message("Something new"),
+            NodeError::Other(String::from("Something new"))
+        );
+        let (url, _) = fixture_node(|action| match action {
+            "process" => (200, String::from(r#"{"error": "Gap previous block"}"#)),
+            _ => (200, String::from(r#"{"error": "Account not found"}"#)),
+        })
+        .await;
+        let rpc = ClientRpc::new(&url).unwrap();
+        // an unopened account is not an error
+        assert!(rpc.account_info(&"0".repeat(64)).await.unwrap().is_none());
+        let err = rpc.process(&test_block()).await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<NodeError>(),
+            Some(&NodeError::GapPrevious)
+        );
+        // the envelope is not mistaken for a response, even one that would fit it
+        let err = rpc.block_known(&"0".repeat(64)).await.unwrap_err();
+        assert_eq!(err, "Account not found");
+    }
+
+    #[tokio::test]
+    async fn node_errors_are_not_retried() {
+        let (rejects, _) =
+            fixture_node(|_| (200, String::from(r#"{"error": "Account not found"}"#))).await;
+        let (other, other_hits) = fixture_node(|_| (200, String::from("{}"))).await;
+        let rpc = ClientRpc::with_endpoints(&[rejects, other], Duration::from_secs(5)).unwrap();
+        assert!(rpc.account_info(&"0".repeat(64)).await.unwrap().is_none());
+        assert_eq!(other_hits.load(Ordering::SeqCst), 0);
+        assert_eq!(rpc.health()[0].consecutive_failures, 0);
     }
 }
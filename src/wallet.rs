@@ -1,7 +1,7 @@
 use crate::account;
 use crate::encoding;
-use futures::lock::Mutex;
-use std::sync::Arc;
+use crate::network::Network;
+use futures::lock::{Mutex, MutexGuard};
 use hex::FromHex;
 use std::convert::TryInto;
 use std::error::Error;
@@ -9,51 +9,114 @@ use std::fs;
 use std::fs::OpenOptions;
 use std::io::{prelude::*, BufReader};
 use std::str;
+use std::sync::Arc;
 
+// live network wallet file. see Network::wallet_file for the others.
 pub const WALLET_FILE_PATH: &str = "nanors.wal";
 
-pub type Accounts = Arc<Mutex<Vec<account::Account>>>;
+// the list lock is only held to look accounts up or add one. each account has its
+// own lock, so work on different accounts runs at the same time while work on one
+// account is done in turn.
+pub type Accounts = Arc<Mutex<Vec<AccountLock>>>;
+
+#[derive(Clone)]
+pub struct AccountLock {
+    pub index: u32,
+    pub addr: String,
+    account: Arc<Mutex<account::Account>>,
+}
+
+impl AccountLock {
+    pub fn new(account: account::Account) -> AccountLock {
+        AccountLock {
+            index: account.index,
+            addr: account.addr.clone(),
+            account: Arc::new(Mutex::new(account)),
+        }
+    }
+
+    pub async fn lock(&self) -> MutexGuard<'_, account::Account> {
+        self.account.lock().await
+    }
+}
+
+pub fn accounts(accounts: Vec<account::Account>) -> Accounts {
+    Arc::new(Mutex::new(
+        accounts.into_iter().map(AccountLock::new).collect(),
+    ))
+}
+
+// a copy of the list, so it is not locked while the accounts are worked on
+pub async fn account_locks(accounts: &Accounts) -> Vec<AccountLock> {
+    accounts.lock().await.clone()
+}
+
+pub async fn find_account(accounts: &Accounts, addr: &str) -> Option<AccountLock> {
+    let accounts = accounts.lock().await;
+    accounts.iter().find(|a| a.addr == addr).cloned()
+}
+
+#[derive(Debug)]
+pub enum WalletError {
+    NotFound(String),
+    AlreadyExists(String),
+    BadPassword,
+}
+
+impl std::fmt::Display for WalletError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            WalletError::NotFound(name) => write!(f, "wallet {} not found", name),
+            WalletError::AlreadyExists(name) => write!(f, "wallet {} already exists", name),
+            WalletError::BadPassword => write!(f, "could not decrypt wallet key"),
+        }
+    }
+}
+
+impl Error for WalletError {}
 
 pub struct Wallet {
     pub name: String,
+    pub network: Network,
     pub accounts: Accounts,
 }
 
 impl Wallet {
-    pub async fn new(name: &str, pw: &str) -> Result<Wallet, Box<dyn Error>> {
+    pub async fn new(name: &str, pw: &str, network: Network) -> Result<Wallet, Box<dyn Error>> {
         let name = String::from(name);
-        if find_local_wallet(&name).is_some() {
-            return Err(format!("wallet {} already exists", name).into());
+        if find_local_wallet(&name, network).is_some() {
+            return Err(WalletError::AlreadyExists(name).into());
         }
         let seed = encoding::generate_nano_seed();
-        let mut accounts = Vec::new();
-        accounts.push(account::Account::new(0, &seed)?);
-        let accounts = Arc::new(Mutex::new(accounts));
-        let wallet = Wallet { name, accounts };
+        let accounts = accounts(vec![account::Account::new(0, &seed, network)?]);
+        let wallet = Wallet {
+            name,
+            network,
+            accounts,
+        };
         wallet.save_wallet(pw, &seed).await?;
         Ok(wallet)
     }
 
     pub async fn add_account(&mut self, pw: &str) -> Result<(), Box<dyn Error>> {
-        let (_, n_acct, seed) = get_wallet_data(&self.name, pw)?;
-        self.accounts
-            .lock()
-            .await
-            .push(account::Account::new(n_acct, &seed)?);
+        let (_, n_acct, seed) = get_wallet_data(&self.name, pw, self.network)?;
+        let account = account::Account::new(n_acct, &seed, self.network)?;
+        self.accounts.lock().await.push(AccountLock::new(account));
         self.save_wallet(pw, &seed).await?;
         Ok(())
     }
 
-    pub fn load(w_name: &str, pw: &str) -> Result<Wallet, Box<dyn Error>> {
-        let (name, n_acct, seed) = get_wallet_data(w_name, pw)?;
+    pub fn load(w_name: &str, pw: &str, network: Network) -> Result<Wallet, Box<dyn Error>> {
+        let (name, n_acct, seed) = get_wallet_data(w_name, pw, network)?;
         if !name.is_empty() && n_acct > 0 {
-            let mut accounts = Vec::new();
+            let mut list = Vec::new();
             for i in 0..n_acct {
-                accounts.push(account::Account::new(i, &seed)?);
+                list.push(account::Account::new(i, &seed, network)?);
             }
             Ok(Wallet {
                 name,
-                accounts: Arc::new(Mutex::new(accounts)),
+                network,
+                accounts: accounts(list),
             })
         } else {
             Err("something went wrong".into())
@@ -70,11 +133,14 @@ impl Wallet {
             hex::encode_upper(&ciphertext),
             hex::encode_upper(&nonce)
         );
-        let mut lines: Vec<String> = fs::read_to_string(WALLET_FILE_PATH)?
-            .lines()
-            .map(|l| l.to_string())
-            .collect();
-        if let Some((_, line_index)) = find_local_wallet(&self.name) {
+        let path = self.network.wallet_file();
+        let existing = match fs::read_to_string(path) {
+            Ok(existing) => existing,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let mut lines: Vec<String> = existing.lines().map(|l| l.to_string()).collect();
+        if let Some((_, line_index)) = find_local_wallet(&self.name, self.network) {
             // remove old wallet if we are overwriting
             lines.remove(line_index);
         }
@@ -83,14 +149,36 @@ impl Wallet {
         let mut file = OpenOptions::new()
             .write(true)
             .create(true)
-            .open(WALLET_FILE_PATH)?;
+            .truncate(true)
+            .open(path)?;
         writeln!(file, "{}", lines)?;
         Ok(())
     }
 }
 
-fn find_local_wallet(find_name: &str) -> Option<(String, usize)> {
-    let file = OpenOptions::new().read(true).open(WALLET_FILE_PATH).ok()?;
+// names of every wallet in the network's local wallet file
+pub fn list_local_wallets(network: Network) -> Result<Vec<String>, Box<dyn Error>> {
+    let file = match OpenOptions::new().read(true).open(network.wallet_file()) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+    let mut names = vec![];
+    for line in BufReader::new(file).lines() {
+        if let Some(name) = line?.split('|').next() {
+            if !name.is_empty() {
+                names.push(String::from(name));
+            }
+        }
+    }
+    Ok(names)
+}
+
+fn find_local_wallet(find_name: &str, network: Network) -> Option<(String, usize)> {
+    let file = OpenOptions::new()
+        .read(true)
+        .open(network.wallet_file())
+        .ok()?;
     let reader = BufReader::new(file);
     for (i, line) in reader.lines().enumerate() {
         let line = line.unwrap();
@@ -102,9 +190,13 @@ fn find_local_wallet(find_name: &str) -> Option<(String, usize)> {
     None
 }
 
-fn get_wallet_data(w_name: &str, pw: &str) -> Result<(String, u32, [u8; 32]), Box<dyn Error>> {
+fn get_wallet_data(
+    w_name: &str,
+    pw: &str,
+    network: Network,
+) -> Result<(String, u32, [u8; 32]), Box<dyn Error>> {
     let (name, n_acct, seed);
-    match find_local_wallet(w_name) {
+    match find_local_wallet(w_name, network) {
         Some((wstr, _)) => {
             let mut wal = wstr.split("|");
             name = String::from(wal.next().ok_or("name not found")?);
@@ -112,11 +204,12 @@ fn get_wallet_data(w_name: &str, pw: &str) -> Result<(String, u32, [u8; 32]), Bo
             n_acct = wallet_data.0;
             let ciphertext = wallet_data.1;
             let nonce = wallet_data.2;
-            seed = encoding::aes_gcm_decrypt(pw.as_bytes(), nonce, &ciphertext, name.as_bytes())?
+            seed = encoding::aes_gcm_decrypt(pw.as_bytes(), nonce, &ciphertext, name.as_bytes())
+                .map_err(|_| WalletError::BadPassword)?
                 .as_slice()
                 .try_into()?;
         }
-        None => return Err(format!("wallet {} not found", w_name).into()),
+        None => return Err(WalletError::NotFound(String::from(w_name)).into()),
     }
     Ok((name, n_acct, seed))
 }
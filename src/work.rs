@@ -1,12 +1,17 @@
+use crate::block;
 use crate::encoding;
+use crate::network::Network;
+use rand::Rng;
+use std::convert::TryInto;
 use std::error::Error;
 use std::sync::{
     mpsc::{self, Receiver, Sender},
     Arc, Mutex,
 };
-//use std::time::SystemTime;
+use std::time::{Duration, Instant};
 
-const POW_LOCAL_WORKERS: u64 = 6;
+pub const POW_LOCAL_WORKERS: u64 = 6;
+// live network thresholds. see network::Network for the others.
 pub const RECV_DIFFICULTY: &str = "fffffe0000000000";
 pub const DEFAULT_DIFFICULTY: &str = "fffffff800000000";
 
@@ -16,50 +21,137 @@ pub enum WorkStatus {
     Finished(String),
 }
 
+// mirrors the fields of the node's work_validate rpc response.
+// https://docs.nano.org/commands/rpc-protocol/#work_validate
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkValidation {
+    pub valid: bool,
+    pub valid_all: bool,
+    pub valid_receive: bool,
+    pub difficulty: String,
+    pub multiplier: f64,
+}
+
+pub fn parse_difficulty(difficulty: &str) -> Result<u64, Box<dyn Error>> {
+    Ok(u64::from_be_bytes(
+        hex::decode(difficulty)?.as_slice().try_into()?,
+    ))
+}
+
+// difficulty value achieved by a work nonce (hex, as found in blocks) against a root.
+pub fn work_difficulty(root: &[u8; 32], work: &str) -> Result<u64, Box<dyn Error>> {
+    let mut nonce: [u8; 8] = hex::decode(work)?.as_slice().try_into()?;
+    nonce.reverse(); // work hex string is BE, hash input is LE
+    Ok(u64::from_le_bytes(encoding::nano_work_hash(root, &nonce)?))
+}
+
+pub fn meets_difficulty(
+    root: &[u8; 32],
+    work: &str,
+    difficulty: &str,
+) -> Result<bool, Box<dyn Error>> {
+    Ok(work_difficulty(root, work)? >= parse_difficulty(difficulty)?)
+}
+
+// multiplier of a difficulty relative to the network's base (send) threshold
+pub fn multiplier(difficulty: u64, network: Network) -> Result<f64, Box<dyn Error>> {
+    let base = parse_difficulty(network.send_difficulty())?;
+    Ok(base.wrapping_neg() as f64 / difficulty.wrapping_neg() as f64)
+}
+
+// difficulty needed to reach a multiplier of the network's base (send) threshold
+pub fn from_multiplier(multiplier: f64, network: Network) -> Result<u64, Box<dyn Error>> {
+    if multiplier <= 0.0 {
+        return Err("multiplier must be positive".into());
+    }
+    let base = parse_difficulty(network.send_difficulty())?;
+    Ok(((base.wrapping_neg() as f64 / multiplier) as u64).wrapping_neg())
+}
+
+pub fn validate_work(
+    root: &[u8; 32],
+    work: &str,
+    subtype: Option<block::SubType>,
+    network: Network,
+) -> Result<WorkValidation, Box<dyn Error>> {
+    let difficulty = work_difficulty(root, work)?;
+    let valid_all = difficulty >= parse_difficulty(network.send_difficulty())?;
+    let valid_receive = difficulty >= parse_difficulty(network.receive_difficulty())?;
+    let valid = match subtype {
+        Some(subtype) => difficulty >= parse_difficulty(network.difficulty_for(subtype))?,
+        None => valid_all,
+    };
+    Ok(WorkValidation {
+        valid,
+        valid_all,
+        valid_receive,
+        difficulty: format!("{:016x}", difficulty),
+        multiplier: multiplier(difficulty, network)?,
+    })
+}
+
 //https://docs.nano.org/integration-guides/work-generation/#work-calculation-details
 pub fn pow_local(previous: [u8; 32], threshold: &[u8; 8]) -> Result<[u8; 8], Box<dyn Error>> {
-    let threshold = threshold.clone();
+    pow_local_with_cancel(previous, threshold, Arc::new(Mutex::new(false)))
+}
+
+// same as pow_local, but gives up with an error once `cancel` is set.
+pub fn pow_local_with_cancel(
+    previous: [u8; 32],
+    threshold: &[u8; 8],
+    cancel: Arc<Mutex<bool>>,
+) -> Result<[u8; 8], Box<dyn Error>> {
+    let (work, _) = pow_local_workers(previous, threshold, POW_LOCAL_WORKERS, cancel)?;
+    Ok(work)
+}
+
+// runs pow over `workers` threads. also returns the number of hashes tried.
+pub fn pow_local_workers(
+    previous: [u8; 32],
+    threshold: &[u8; 8],
+    workers: u64,
+    found: Arc<Mutex<bool>>,
+) -> Result<([u8; 8], u64), Box<dyn Error>> {
+    if workers == 0 {
+        return Err("pow needs at least one worker".into());
+    }
+    let threshold = *threshold;
     let (tx, rx): (Sender<[u8; 8]>, Receiver<[u8; 8]>) = mpsc::channel();
-    let found = Arc::new(Mutex::new(false));
-    //let now = SystemTime::now();
     let mut handles = vec![];
     // dispatch workers
-    for i in 0..POW_LOCAL_WORKERS {
+    for i in 0..workers {
         let (sender, arc) = (tx.clone(), found.clone());
         let handle = std::thread::spawn(move || {
-            pow_local_segment(i, &previous, &threshold, sender, arc);
+            pow_local_segment(i, workers, &previous, &threshold, sender, arc)
         });
         handles.push(handle);
     }
-    let mut work = rx.recv().unwrap(); // recv will block.
-                                       /*
-                                       let elapsed_min = (now.elapsed()?.as_secs()) as f64 / 60.0;
-                                       println!(
-                                           "pow complete in {} minutes. work: {:02x?} -> {:02x?}",
-                                           elapsed_min,
-                                           work,
-                                           encoding::nano_work_hash(&previous, &work)
-                                       );
-                                       */
+    drop(tx); // recv errors once every worker has stopped without a result
+    let work = rx.recv(); // recv will block.
     *found.lock().unwrap() = true;
+    let mut hashes = 0;
     for handle in handles {
-        handle.join().unwrap();
+        hashes += handle.join().unwrap();
     }
+    let mut work = work.map_err(|_| "work cancelled")?;
     work.reverse(); // work hex string seems to be LE
-    Ok(work)
+    Ok((work, hashes))
 }
 
 fn pow_local_segment(
     i: u64,
+    workers: u64,
     previous: &[u8; 32],
     threshold: &[u8; 8],
     sender: Sender<[u8; 8]>,
     found: Arc<Mutex<bool>>,
-) {
-    let seg_size = 0xffffffffffffffff / POW_LOCAL_WORKERS;
+) -> u64 {
+    let seg_size = 0xffffffffffffffff / workers;
     let (low, high) = (seg_size * i, seg_size * (i + 1));
+    let mut hashes = 0;
     for nonce in low..high {
         let nonce = nonce.to_le_bytes();
+        hashes += 1;
         if let Ok(output) = encoding::nano_work_hash(previous, &nonce) {
             // blake2b output in le
             if u64::from_le_bytes(output) >= u64::from_be_bytes(*threshold) {
@@ -71,4 +163,109 @@ fn pow_local_segment(
             break;
         }
     }
+    hashes
+}
+
+#[derive(Debug, Clone)]
+pub struct BenchResult {
+    pub difficulty: String,
+    pub threads: u64,
+    pub runs: usize,
+    pub invalid: usize,
+    pub mean: Duration,
+    pub p95: Duration,
+    pub hashes_per_sec: f64,
+}
+
+// generates work for `runs` random roots and checks every result against the difficulty.
+pub fn benchmark(
+    runs: usize,
+    difficulty: &str,
+    threads: u64,
+) -> Result<BenchResult, Box<dyn Error>> {
+    if runs == 0 {
+        return Err("benchmark needs at least one run".into());
+    }
+    let threshold: [u8; 8] = hex::decode(difficulty)?.as_slice().try_into()?;
+    let (mut times, mut hashes, mut invalid) = (vec![], 0, 0);
+    for _ in 0..runs {
+        let root = rand::thread_rng().gen::<[u8; 32]>();
+        let now = Instant::now();
+        let (work, n) = pow_local_workers(root, &threshold, threads, Arc::new(Mutex::new(false)))?;
+        times.push(now.elapsed());
+        hashes += n;
+        if !meets_difficulty(&root, &hex::encode(work), difficulty)? {
+            invalid += 1;
+        }
+    }
+    let total: Duration = times.iter().sum();
+    times.sort();
+    // nearest rank
+    let p95 = times[((runs as f64 * 0.95).ceil() as usize).max(1) - 1];
+    Ok(BenchResult {
+        difficulty: String::from(difficulty),
+        threads,
+        runs,
+        invalid,
+        mean: total / runs as u32,
+        p95,
+        hashes_per_sec: hashes as f64 / total.as_secs_f64(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const TEST_ROOT: &str = "611C5C60034E6AD9ED9591E62DD1A78B482C2EDF1A02C5E063E5ABE692AED065";
+
+    #[test]
+    fn validates_work() {
+        let root: [u8; 32] = hex::decode(TEST_ROOT).unwrap().try_into().unwrap();
+        let v = validate_work(&root, "08d09dc3405d9441", None, Network::Live).unwrap();
+        assert!(v.valid_receive);
+        assert!(
+            parse_difficulty(&v.difficulty).unwrap()
+                >= parse_difficulty("ffffffc000000000").unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_work_for_other_root() {
+        let root = [0u8; 32];
+        let v = validate_work(
+            &root,
+            "08d09dc3405d9441",
+            Some(block::SubType::Receive),
+            Network::Live,
+        )
+        .unwrap();
+        assert!(!v.valid);
+        assert!(!v.valid_all);
+    }
+
+    #[test]
+    fn local_work_is_valid() {
+        let root: [u8; 32] = hex::decode(TEST_ROOT).unwrap().try_into().unwrap();
+        let threshold: [u8; 8] = hex::decode("fff0000000000000").unwrap().try_into().unwrap();
+        let work = hex::encode(pow_local(root, &threshold).unwrap());
+        let difficulty = work_difficulty(&root, &work).unwrap();
+        assert!(difficulty >= u64::from_be_bytes(threshold));
+    }
+
+    #[test]
+    fn cancelled_work_errors() {
+        let threshold = [0xffu8; 8];
+        let cancel = Arc::new(Mutex::new(true));
+        assert!(pow_local_with_cancel([0u8; 32], &threshold, cancel).is_err());
+    }
+
+    #[test]
+    fn benchmark_verifies_runs() {
+        let b = benchmark(5, "f000000000000000", 2).unwrap();
+        assert_eq!(b.runs, 5);
+        assert_eq!(b.invalid, 0);
+        assert!(b.hashes_per_sec > 0.0);
+    }
 }
@@ -0,0 +1,367 @@
+// a local stand-in for the nano-work-server http api, backed by work::pow_local.
+// https://github.com/nanocurrency/nano-work-server
+// https://docs.nano.org/integration-guides/work-generation/#work-server
+use crate::network::Network;
+use crate::work;
+use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::convert::Infallible;
+use std::convert::TryInto;
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{oneshot, Notify};
+
+pub const DEFAULT_WORK_SERVER_ADDR: &str = "127.0.0.1:7076";
+
+#[derive(Deserialize, Debug)]
+struct WorkReq {
+    action: String,
+    hash: Option<String>,
+    work: Option<String>,
+    difficulty: Option<String>,
+    multiplier: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct WorkGenerateResp {
+    work: String,
+    difficulty: String,
+    multiplier: String,
+    hash: String,
+}
+
+#[derive(Serialize, Debug)]
+struct WorkValidateResp {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    valid: Option<String>,
+    valid_all: String,
+    valid_receive: String,
+    difficulty: String,
+    multiplier: String,
+}
+
+#[derive(Serialize, Debug)]
+struct WorkCancelResp {
+    success: String,
+}
+
+#[derive(Serialize, Debug)]
+struct WorkErrorResp {
+    error: String,
+}
+
+struct Waiter {
+    difficulty: u64,
+    tx: oneshot::Sender<Result<String, String>>,
+}
+
+#[derive(Default)]
+struct QueueState {
+    order: VecDeque<[u8; 32]>,
+    waiters: HashMap<[u8; 32], Vec<Waiter>>,
+    active: Option<([u8; 32], Arc<Mutex<bool>>)>,
+}
+
+// requests are served one root at a time (pow_local already uses every worker thread).
+// concurrent requests for the same root share a single generation.
+#[derive(Default)]
+pub struct WorkQueue {
+    state: Mutex<QueueState>,
+    notify: Notify,
+}
+
+impl WorkQueue {
+    pub fn new() -> Arc<WorkQueue> {
+        let queue = Arc::new(WorkQueue::default());
+        tokio::spawn(WorkQueue::run(queue.clone()));
+        queue
+    }
+
+    pub async fn generate(&self, root: [u8; 32], difficulty: u64) -> Result<String, String> {
+        let (tx, rx) = oneshot::channel();
+        {
+            let mut state = self.state.lock().unwrap();
+            let running = match &state.active {
+                Some((r, cancel)) => *r == root && !*cancel.lock().unwrap(),
+                None => false,
+            };
+            if !running && !state.order.contains(&root) {
+                state.order.push_back(root);
+            }
+            state
+                .waiters
+                .entry(root)
+                .or_default()
+                .push(Waiter { difficulty, tx });
+        }
+        self.notify.notify_one();
+        rx.await.unwrap_or_else(|_| Err("work cancelled".into()))
+    }
+
+    pub fn cancel(&self, root: &[u8; 32]) {
+        let mut state = self.state.lock().unwrap();
+        state.order.retain(|r| r != root);
+        for w in state.waiters.remove(root).unwrap_or_default() {
+            let _ = w.tx.send(Err("work cancelled".into()));
+        }
+        if let Some((r, cancel)) = &state.active {
+            if r == root {
+                *cancel.lock().unwrap() = true;
+            }
+        }
+    }
+
+    async fn run(queue: Arc<WorkQueue>) {
+        loop {
+            let next = {
+                let mut state = queue.state.lock().unwrap();
+                let mut next = None;
+                while let Some(root) = state.order.pop_front() {
+                    let difficulty = state
+                        .waiters
+                        .get(&root)
+                        .and_then(|ws| ws.iter().map(|w| w.difficulty).max());
+                    if let Some(difficulty) = difficulty {
+                        let cancel = Arc::new(Mutex::new(false));
+                        state.active = Some((root, cancel.clone()));
+                        next = Some((root, difficulty, cancel));
+                        break;
+                    }
+                }
+                next
+            };
+            let (root, difficulty, cancel) = match next {
+                Some(n) => n,
+                None => {
+                    queue.notify.notified().await;
+                    continue;
+                }
+            };
+            let threshold = difficulty.to_be_bytes();
+            let flag = cancel.clone();
+            let result = tokio::task::spawn_blocking(move || {
+                work::pow_local_with_cancel(root, &threshold, flag)
+                    .map(hex::encode)
+                    .map_err(|e| e.to_string())
+            })
+            .await
+            .unwrap_or_else(|e| Err(e.to_string()));
+            queue.finish(root, result, *cancel.lock().unwrap());
+        }
+    }
+
+    fn finish(&self, root: [u8; 32], result: Result<String, String>, cancelled: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.active = None;
+        let waiters = state.waiters.remove(&root).unwrap_or_default();
+        let mut unmet = vec![];
+        match result {
+            Ok(w) => {
+                let achieved = work::work_difficulty(&root, &w).unwrap_or(0);
+                for waiter in waiters {
+                    if achieved >= waiter.difficulty {
+                        let _ = waiter.tx.send(Ok(w.clone()));
+                    } else {
+                        // asked for more than this run targeted, go again
+                        unmet.push(waiter);
+                    }
+                }
+            }
+            // anyone still waiting asked again after the cancel, and is already requeued
+            Err(_) if cancelled => unmet = waiters,
+            Err(e) => {
+                for waiter in waiters {
+                    let _ = waiter.tx.send(Err(e.clone()));
+                }
+            }
+        }
+        if !unmet.is_empty() {
+            state.waiters.insert(root, unmet);
+            if !state.order.contains(&root) {
+                state.order.push_front(root);
+            }
+        }
+    }
+}
+
+// difficulties, multipliers and validation are relative to `network`'s thresholds
+pub async fn serve(addr: SocketAddr, network: Network) -> Result<(), Box<dyn Error>> {
+    let queue = WorkQueue::new();
+    let make_svc = make_service_fn(move |_conn| {
+        let queue = queue.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |req| handle(queue.clone(), network, req))) }
+    });
+    Server::try_bind(&addr)?.serve(make_svc).await?;
+    Ok(())
+}
+
+async fn handle(
+    queue: Arc<WorkQueue>,
+    network: Network,
+    req: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    let body = match hyper::body::to_bytes(req.into_body()).await {
+        Ok(b) => b,
+        Err(e) => return Ok(json_response(error_json(e.to_string()))),
+    };
+    let resp = match serde_json::from_slice::<WorkReq>(&body) {
+        Ok(r) => dispatch(&queue, network, r)
+            .await
+            .unwrap_or_else(error_json),
+        Err(e) => error_json(format!("unable to parse json: {}", e)),
+    };
+    Ok(json_response(resp))
+}
+
+async fn dispatch(queue: &WorkQueue, network: Network, req: WorkReq) -> Result<String, String> {
+    let hash = req.hash.as_deref().ok_or("missing hash")?;
+    let root: [u8; 32] = hex::decode(hash)
+        .ok()
+        .and_then(|r| r.as_slice().try_into().ok())
+        .ok_or("bad block hash")?;
+    let resp = match req.action.as_str() {
+        "work_generate" => {
+            let difficulty = req_difficulty(&req, network)?;
+            let w = queue.generate(root, difficulty).await?;
+            let achieved = work::work_difficulty(&root, &w).map_err(|e| e.to_string())?;
+            serde_json::to_string(&WorkGenerateResp {
+                work: w,
+                difficulty: format!("{:016x}", achieved),
+                multiplier: work::multiplier(achieved, network)
+                    .map_err(|e| e.to_string())?
+                    .to_string(),
+                hash: hash.to_string(),
+            })
+        }
+        "work_validate" => {
+            let w = req.work.as_deref().ok_or("missing work")?;
+            let difficulty = req_difficulty(&req, network)?;
+            let v = work::validate_work(&root, w, None, network).map_err(|e| e.to_string())?;
+            let achieved = work::parse_difficulty(&v.difficulty).map_err(|e| e.to_string())?;
+            // like the node, only report `valid` when a difficulty was asked for
+            let asked = req.difficulty.is_some() || req.multiplier.is_some();
+            serde_json::to_string(&WorkValidateResp {
+                valid: asked.then(|| bool_str(achieved >= difficulty)),
+                valid_all: bool_str(v.valid_all),
+                valid_receive: bool_str(v.valid_receive),
+                difficulty: v.difficulty,
+                multiplier: v.multiplier.to_string(),
+            })
+        }
+        // cancels whatever is running for the root, the difficulty does not matter
+        "work_cancel" => {
+            queue.cancel(&root);
+            serde_json::to_string(&WorkCancelResp {
+                success: String::new(),
+            })
+        }
+        _ => return Err(format!("unknown action {}", req.action)),
+    };
+    resp.map_err(|e| e.to_string())
+}
+
+fn req_difficulty(req: &WorkReq, network: Network) -> Result<u64, String> {
+    if let Some(d) = &req.difficulty {
+        return work::parse_difficulty(d).map_err(|_| "bad difficulty".into());
+    }
+    if let Some(m) = &req.multiplier {
+        let m = m.parse::<f64>().map_err(|_| "bad multiplier")?;
+        return work::from_multiplier(m, network).map_err(|e| e.to_string());
+    }
+    work::parse_difficulty(network.send_difficulty()).map_err(|e| e.to_string())
+}
+
+fn bool_str(b: bool) -> String {
+    String::from(if b { "1" } else { "0" })
+}
+
+fn error_json(error: String) -> String {
+    serde_json::to_string(&WorkErrorResp { error }).unwrap()
+}
+
+fn json_response(body: String) -> Response<Body> {
+    let mut resp = Response::new(Body::from(body));
+    resp.headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const EASY_DIFFICULTY: u64 = 0xf000000000000000;
+
+    #[tokio::test]
+    async fn dedups_identical_roots() {
+        let queue = WorkQueue::new();
+        let root = [7u8; 32];
+        let (a, b) = tokio::join!(
+            queue.generate(root, EASY_DIFFICULTY),
+            queue.generate(root, EASY_DIFFICULTY)
+        );
+        assert_eq!(a.unwrap(), b.unwrap());
+    }
+
+    #[tokio::test]
+    async fn serves_harder_waiter_on_shared_root() {
+        let queue = WorkQueue::new();
+        let root = [9u8; 32];
+        let harder = 0xff00000000000000;
+        let (_, b) = tokio::join!(
+            queue.generate(root, EASY_DIFFICULTY),
+            queue.generate(root, harder)
+        );
+        assert!(work::work_difficulty(&root, &b.unwrap()).unwrap() >= harder);
+    }
+
+    #[tokio::test]
+    async fn cancel_rejects_waiters() {
+        let queue = WorkQueue::new();
+        let root = [3u8; 32];
+        let q = queue.clone();
+        let gen = tokio::spawn(async move { q.generate(root, u64::MAX).await });
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        queue.cancel(&root);
+        assert!(gen.await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn validates_over_dispatch() {
+        let queue = WorkQueue::new();
+        let req: WorkReq = serde_json::from_str(
+            r#"{"action": "work_validate",
+                "hash": "611C5C60034E6AD9ED9591E62DD1A78B482C2EDF1A02C5E063E5ABE692AED065",
+                "work": "08d09dc3405d9441",
+                "difficulty": "ffffffc000000000"}"#,
+        )
+        .unwrap();
+        let resp: serde_json::Value =
+            serde_json::from_str(&dispatch(&queue, Network::Live, req).await.unwrap()).unwrap();
+        assert_eq!(resp["valid"], "1");
+        assert_eq!(resp["valid_receive"], "1");
+    }
+
+    #[tokio::test]
+    async fn cancels_over_dispatch_whatever_the_difficulty() {
+        let queue = WorkQueue::new();
+        let root = [5u8; 32];
+        let q = queue.clone();
+        let gen = tokio::spawn(async move { q.generate(root, u64::MAX).await });
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        let req = WorkReq {
+            action: String::from("work_cancel"),
+            hash: Some(hex::encode(root)),
+            work: None,
+            difficulty: Some(String::from("not a difficulty")),
+            multiplier: None,
+        };
+        dispatch(&queue, Network::Live, req).await.unwrap();
+        assert!(gen.await.unwrap().is_err());
+    }
+}
@@ -1,20 +1,278 @@
 use crate::block;
+use crate::rpc;
 
-use futures::stream::{SplitSink, SplitStream};
-use futures_util::{SinkExt, StreamExt};
+use futures::stream::{SplitSink, SplitStream, Stream};
+use futures_util::{FutureExt, SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 use tokio::net::TcpStream;
 use tokio::sync::mpsc;
-use tokio::time;
+use tokio::task::JoinHandle;
+use tokio::time::{self, Duration, Instant};
 use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
 use url::Url;
 
+// wait before reconnecting. doubles while reconnecting keeps failing.
+const RECONNECT_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(60);
+pub const DEFAULT_PING_INTERVAL: Duration = Duration::from_secs(30);
+pub const DEFAULT_ACK_TIMEOUT: Duration = Duration::from_secs(10);
+pub const DEFAULT_STALE_AFTER: Duration = Duration::from_secs(75);
+
+type WSWrite = SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>;
+type WSRead = SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>;
+
+#[derive(Debug)]
+pub enum WSEvent {
+    Notification(Box<Notification>),
+    // a subscribed connection dropped, with why. reconnecting follows.
+    Disconnected(String),
+    // the connection dropped and is back. notifications sent meanwhile were missed.
+    Reconnected,
+    // a connection attempt that failed and will be retried, or a message that could
+    // not be read
+    Error(String),
+}
+
+// how a connection is kept alive. subscriptions, account updates and pings are sent
+// with an id and have to be acked within ack_timeout.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WSOptions {
+    pub ping_interval: Duration,
+    pub ack_timeout: Duration,
+    // reconnect when nothing at all arrived for this long
+    pub stale_after: Duration,
+}
+
+impl Default for WSOptions {
+    fn default() -> Self {
+        WSOptions {
+            ping_interval: DEFAULT_PING_INTERVAL,
+            ack_timeout: DEFAULT_ACK_TIMEOUT,
+            stale_after: DEFAULT_STALE_AFTER,
+        }
+    }
+}
+
+// https://docs.nano.org/integration-guides/websockets/#available-topics
+#[derive(Debug, Clone, PartialEq)]
+pub enum Topic {
+    Confirmation(ConfirmationOptions),
+    Votes(VotesOptions),
+    StoppedElection,
+    ActiveDifficulty,
+    Work,
+    Telemetry,
+    NewUnconfirmedBlock,
+    Bootstrap,
+}
+
+// the default is every confirmed block, with the block and without election info
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct ConfirmationOptions {
+    // None for every account
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accounts: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confirmation_type: Option<ConfirmationType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_election_info: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_block: Option<bool>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfirmationType {
+    All,
+    Active,
+    ActiveQuorum,
+    ActiveConfirmationHeight,
+    Inactive,
+}
+
+// the default is every representative's live votes
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct VotesOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub representatives: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_replays: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_indeterminate: Option<bool>,
+}
+
+impl ConfirmationOptions {
+    pub fn accounts(accounts: Vec<String>) -> ConfirmationOptions {
+        ConfirmationOptions {
+            accounts: Some(accounts),
+            ..Default::default()
+        }
+    }
+}
+
+impl Topic {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Topic::Confirmation(_) => "confirmation",
+            Topic::Votes(_) => "vote",
+            Topic::StoppedElection => "stopped_election",
+            Topic::ActiveDifficulty => "active_difficulty",
+            Topic::Work => "work",
+            Topic::Telemetry => "telemetry",
+            Topic::NewUnconfirmedBlock => "new_unconfirmed_block",
+            Topic::Bootstrap => "bootstrap",
+        }
+    }
+
+    fn request(&self) -> Result<WSSubscribeReq, serde_json::Error> {
+        let options = match self {
+            Topic::Confirmation(o) => Some(serde_json::to_value(o)?),
+            Topic::Votes(o) => Some(serde_json::to_value(o)?),
+            _ => None,
+        };
+        Ok(WSSubscribeReq {
+            action: String::from("subscribe"),
+            topic: String::from(self.name()),
+            options,
+        })
+    }
+
+    // the accounts SubscriptionHandle updates
+    fn accounts_mut(&mut self) -> Option<&mut Vec<String>> {
+        match self {
+            Topic::Confirmation(o) => o.accounts.as_mut(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Notification {
+    // milliseconds since the epoch, from the node
+    pub time: Option<String>,
+    pub message: TopicMessage,
+}
+
+#[derive(Debug)]
+pub enum TopicMessage {
+    Confirmation(WSConfirmationMessage),
+    Vote(VoteMessage),
+    StoppedElection(StoppedElectionMessage),
+    ActiveDifficulty(ActiveDifficultyMessage),
+    Work(WorkMessage),
+    Telemetry(TelemetryMessage),
+    NewUnconfirmedBlock(block::NanoBlock),
+    Bootstrap(BootstrapMessage),
+}
+
+impl TopicMessage {
+    // None for topics we do not know
+    fn parse(topic: &str, message: Value) -> Result<Option<TopicMessage>, serde_json::Error> {
+        let m = match topic {
+            "confirmation" => TopicMessage::Confirmation(serde_json::from_value(message)?),
+            "vote" => TopicMessage::Vote(serde_json::from_value(message)?),
+            "stopped_election" => TopicMessage::StoppedElection(serde_json::from_value(message)?),
+            "active_difficulty" => TopicMessage::ActiveDifficulty(serde_json::from_value(message)?),
+            "work" => TopicMessage::Work(serde_json::from_value(message)?),
+            "telemetry" => TopicMessage::Telemetry(serde_json::from_value(message)?),
+            "new_unconfirmed_block" => {
+                TopicMessage::NewUnconfirmedBlock(serde_json::from_value(message)?)
+            }
+            "bootstrap" => TopicMessage::Bootstrap(serde_json::from_value(message)?),
+            _ => return Ok(None),
+        };
+        Ok(Some(m))
+    }
+}
+
+// changes to the accounts a running subscription watches
+#[derive(Debug, Clone)]
+pub enum WSUpdate {
+    Add(Vec<String>),
+    Remove(Vec<String>),
+}
+
+// adds and removes the accounts of a confirmation subscription without
+// resubscribing. cloneable, the subscription keeps going when every handle is dropped.
+#[derive(Debug, Clone)]
+pub struct SubscriptionHandle {
+    tx: mpsc::UnboundedSender<WSUpdate>,
+}
+
+impl SubscriptionHandle {
+    // the receiver goes to subscribe_topics
+    pub fn new() -> (SubscriptionHandle, mpsc::UnboundedReceiver<WSUpdate>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (SubscriptionHandle { tx }, rx)
+    }
+
+    pub fn add_accounts(&self, accounts: Vec<String>) -> Result<(), String> {
+        self.update(WSUpdate::Add(accounts))
+    }
+
+    pub fn remove_accounts(&self, accounts: Vec<String>) -> Result<(), String> {
+        self.update(WSUpdate::Remove(accounts))
+    }
+
+    fn update(&self, update: WSUpdate) -> Result<(), String> {
+        self.tx
+            .send(update)
+            .map_err(|_| String::from("ws subscription ended"))
+    }
+}
+
+impl WSUpdate {
+    // false when no topic filters by account, the node has nothing to update then
+    fn apply_to(&self, topics: &mut [Topic]) -> bool {
+        let mut applied = false;
+        for accounts in topics.iter_mut().filter_map(Topic::accounts_mut) {
+            self.apply(accounts);
+            applied = true;
+        }
+        applied
+    }
+
+    fn apply(&self, accounts: &mut Vec<String>) {
+        match self {
+            WSUpdate::Add(add) => {
+                for a in add {
+                    if !accounts.contains(a) {
+                        accounts.push(a.clone());
+                    }
+                }
+            }
+            WSUpdate::Remove(del) => accounts.retain(|a| !del.contains(a)),
+        }
+    }
+
+    // https://docs.nano.org/integration-guides/websockets/#update
+    fn request(&self) -> WSUpdateReq {
+        let (accounts_add, accounts_del) = match self {
+            WSUpdate::Add(add) => (add.clone(), vec![]),
+            WSUpdate::Remove(del) => (vec![], del.clone()),
+        };
+        WSUpdateReq {
+            action: String::from("update"),
+            topic: String::from("confirmation"),
+            options: WSUpdateOptionsReq {
+                accounts_add,
+                accounts_del,
+            },
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct WSNanoResp {
     topic: Option<String>,
     time: Option<String>,
     ack: Option<String>,
-    message: Option<WSConfirmationMessage>,
+    id: Option<String>,
+    message: Option<Value>,
 }
 
 // https://docs.nano.org/integration-guides/websockets/#confirmations
@@ -23,19 +281,145 @@ pub struct WSConfirmationMessage {
     pub account: String,
     pub amount: String,
     pub hash: String,
-    pub block: block::NanoBlock,
+    pub confirmation_type: Option<String>,
+    // with include_election_info
+    pub election_info: Option<ElectionInfo>,
+    // left out when include_block is false
+    pub block: Option<block::NanoBlock>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ElectionInfo {
+    pub duration: String,
+    pub time: String,
+    pub tally: String,
+    #[serde(rename = "final")]
+    pub final_tally: Option<String>,
+    pub blocks: String,
+    pub voters: String,
+    pub request_count: Option<String>,
+}
+
+// https://docs.nano.org/integration-guides/websockets/#votes
+#[derive(Serialize, Deserialize, Debug)]
+pub struct VoteMessage {
+    pub account: String,
+    pub signature: String,
+    pub sequence: Option<String>,
+    pub timestamp: Option<String>,
+    pub blocks: Vec<String>,
+    // vote, replay or indeterminate
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+// https://docs.nano.org/integration-guides/websockets/#stopped-elections
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StoppedElectionMessage {
+    pub hash: String,
+}
+
+// https://docs.nano.org/integration-guides/websockets/#active-difficulty
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ActiveDifficultyMessage {
+    pub multiplier: String,
+    pub network_current: String,
+    pub network_minimum: String,
+    pub network_receive_current: Option<String>,
+    pub network_receive_minimum: Option<String>,
+}
+
+// https://docs.nano.org/integration-guides/websockets/#proof-of-work
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WorkMessage {
+    pub success: String,
+    pub reason: String,
+    pub duration: String,
+    pub request: WorkRequestInfo,
+    // empty when the work was cancelled or failed
+    #[serde(default, deserialize_with = "rpc::empty_string_as_default")]
+    pub result: WorkResultInfo,
+    #[serde(default, deserialize_with = "rpc::empty_string_as_default")]
+    pub bad_peers: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WorkRequestInfo {
+    pub version: Option<String>,
+    pub hash: String,
+    pub difficulty: String,
+    pub multiplier: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct WorkResultInfo {
+    pub source: String,
+    pub work: String,
+    pub difficulty: String,
+    pub multiplier: String,
+}
+
+// https://docs.nano.org/integration-guides/websockets/#telemetry
+// the node's telemetry_ack plus where it came from. missing fields are left empty.
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct TelemetryMessage {
+    pub block_count: String,
+    pub cemented_count: String,
+    pub unchecked_count: String,
+    pub account_count: String,
+    pub bandwidth_cap: String,
+    pub peer_count: String,
+    pub protocol_version: String,
+    pub uptime: String,
+    pub genesis_block: String,
+    pub major_version: String,
+    pub minor_version: String,
+    pub patch_version: String,
+    pub pre_release_version: String,
+    pub maker: String,
+    pub timestamp: String,
+    pub active_difficulty: String,
+    pub node_id: String,
+    pub signature: String,
+    pub address: String,
+    pub port: String,
+}
+
+// https://docs.nano.org/integration-guides/websockets/#bootstrap
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BootstrapMessage {
+    // started or exited
+    pub reason: String,
+    pub id: String,
+    pub mode: String,
+    // when exited
+    pub total_blocks: Option<String>,
+    pub duration: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
-struct WSConfirmationReq {
+struct WSSubscribeReq {
     action: String,
     topic: String,
-    options: WSConfirmationOptionsReq,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    options: Option<Value>,
 }
 
 #[derive(Serialize, Deserialize)]
-struct WSConfirmationOptionsReq {
-    accounts: Vec<String>,
+struct WSUpdateReq {
+    action: String,
+    topic: String,
+    options: WSUpdateOptionsReq,
+}
+
+#[derive(Serialize, Deserialize)]
+struct WSUpdateOptionsReq {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    accounts_add: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    accounts_del: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -43,78 +427,365 @@ struct WSPingReq {
     action: String,
 }
 
-// todo:? generalize to run, and move subscribe logic
+// topic notifications as a stream, for use without a Manager. reconnects like
+// subscribe_topics and stops when dropped.
+pub struct Subscription {
+    rx: mpsc::Receiver<WSEvent>,
+    handle: SubscriptionHandle,
+    task: JoinHandle<()>,
+}
+
+impl Subscription {
+    pub fn handle(&self) -> SubscriptionHandle {
+        self.handle.clone()
+    }
+}
+
+impl Stream for Subscription {
+    type Item = WSEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<WSEvent>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+pub fn subscribe(
+    ws_host: &str,
+    topics: Vec<Topic>,
+    options: WSOptions,
+) -> Result<Subscription, Box<dyn std::error::Error>> {
+    let ws_host = String::from(Url::parse(ws_host)?.as_str());
+    let (handle, updates) = SubscriptionHandle::new();
+    let (tx, rx) = mpsc::channel(20);
+    let errors = tx.clone();
+    let task = tokio::spawn(async move {
+        let subscribed = subscribe_topics(&ws_host, topics, tx, updates, options).await;
+        // the last event before the stream ends
+        if let Err(e) = subscribed.map_err(|e| e.to_string()) {
+            let _ = errors.send(WSEvent::Error(e)).await;
+        }
+    });
+    Ok(Subscription { rx, handle, task })
+}
+
+// confirmations for `accounts`, see subscribe_topics
 pub async fn subscribe_confirmation(
     ws_host: &str,
     accounts: Vec<String>,
-    sender: mpsc::Sender<WSConfirmationMessage>,
+    sender: mpsc::Sender<WSEvent>,
+    updates: mpsc::UnboundedReceiver<WSUpdate>,
+    options: WSOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let url = Url::parse(ws_host)?;
-    let (ws_stream, _) = connect_async(url).await?;
-    let (mut write, read) = ws_stream.split();
-    subscribe_confirmations(&mut write, accounts).await?;
-    let out = tokio::select! {
-        res = async {
-            watch_connection(read, sender).await?;
-            Ok::<_, Box<dyn std::error::Error>>(())
-        } => {
-            res?;
-        }
-        res = async {
-            keep_alive(&mut write).await?;
-            Ok::<_, Box<dyn std::error::Error>>(())
-        } => {
-            res?;
-        }
-        // oneshot cancel needed?
-    };
-
-    Ok(())
-}
-
-async fn watch_connection(
-    mut stream: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
-    sender: mpsc::Sender<WSConfirmationMessage>,
+    let topics = vec![Topic::Confirmation(ConfirmationOptions::accounts(accounts))];
+    subscribe_topics(ws_host, topics, sender, updates, options).await
+}
+
+// keeps a subscription to `topics` going until `sender` is dropped. `updates`
+// change the watched accounts of confirmation topics, see SubscriptionHandle. a
+// connection that drops, goes quiet or leaves a request unacked is retried with
+// backoff, subscribing to the accounts watched by then. a Disconnected event tells
+// of the drop, an Error event of each failed attempt, and a Reconnected event follows
+// once the node acked the new subscriptions.
+pub async fn subscribe_topics(
+    ws_host: &str,
+    mut topics: Vec<Topic>,
+    sender: mpsc::Sender<WSEvent>,
+    mut updates: mpsc::UnboundedReceiver<WSUpdate>,
+    options: WSOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    while let Some(msg) = stream.next().await {
-        let msg = msg?.into_text()?;
-        let nr: WSNanoResp = serde_json::from_str(msg.as_str())?;
-        if nr.message.is_some() {
-            //println!("\n\nsend conf:\n\n{:#?}", msg);
-            sender.send(nr.message.unwrap()).await?;
+    let url = Url::parse(ws_host)?;
+    let mut backoff = RECONNECT_BACKOFF;
+    let mut connected = false;
+    loop {
+        // changes made while disconnected are part of the new subscription
+        while let Some(Some(update)) = updates.recv().now_or_never() {
+            update.apply_to(&mut topics);
         }
+        let event = match connect_async(&url).await {
+            Ok((ws_stream, _)) => {
+                let mut conn = Connection::new(ws_stream, options, connected);
+                let err = conn.run(&sender, &mut topics, &mut updates).await;
+                if conn.subscribed {
+                    backoff = RECONNECT_BACKOFF;
+                    connected = true;
+                    WSEvent::Disconnected(err)
+                } else {
+                    WSEvent::Error(retrying(&err, backoff))
+                }
+            }
+            Err(e) => WSEvent::Error(retrying(&e.to_string(), backoff)),
+        };
+        // nobody listening any more
+        if sender.send(event).await.is_err() {
+            return Ok(());
+        }
+        time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_RECONNECT_BACKOFF);
     }
-    Err("ws: confirmation ended".into())
 }
 
-async fn subscribe_confirmations(
-    stream: &mut SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>,
-    accounts: Vec<String>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // subscribe to addresses
-    let req = WSConfirmationReq {
-        action: String::from("subscribe"),
-        topic: String::from("confirmation"),
-        options: WSConfirmationOptionsReq { accounts },
-    };
-    let req = match serde_json::to_string(&req) {
-        Ok(req) => req,
-        Err(e) => return Err(e.into()),
-    };
-    stream.send(Message::text(req)).await?;
-    Ok(())
-}
-
-async fn keep_alive(
-    stream: &mut SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let req = WSPingReq {
-        action: String::from("ping"),
-    };
-    loop {
-        time::sleep(time::Duration::from_millis(40000)).await;
-        let req = serde_json::to_string(&req)?;
-        //println!("ping!");
-        stream.send(Message::text(req)).await?;
+fn retrying(err: &str, backoff: Duration) -> String {
+    format!("ws connection failed: {}. retrying in {:?}", err, backoff)
+}
+
+// one websocket connection and the requests it has not had an ack for
+struct Connection {
+    write: WSWrite,
+    read: WSRead,
+    options: WSOptions,
+    // send Reconnected once subscribed
+    reconnect: bool,
+    subscribed: bool,
+    last_id: u64,
+    // id -> action and when its ack is due
+    pending: HashMap<String, (String, Instant)>,
+    last_heard: Instant,
+}
+
+impl Connection {
+    fn new(
+        ws_stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+        options: WSOptions,
+        reconnect: bool,
+    ) -> Connection {
+        let (write, read) = ws_stream.split();
+        Connection {
+            write,
+            read,
+            options,
+            reconnect,
+            subscribed: false,
+            last_id: 0,
+            pending: HashMap::new(),
+            last_heard: Instant::now(),
+        }
+    }
+
+    // returns why the connection ended
+    async fn run(
+        &mut self,
+        sender: &mpsc::Sender<WSEvent>,
+        topics: &mut [Topic],
+        updates: &mut mpsc::UnboundedReceiver<WSUpdate>,
+    ) -> String {
+        match self.watch(sender, topics, updates).await {
+            Ok(()) => String::from("closed"),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    async fn watch(
+        &mut self,
+        sender: &mpsc::Sender<WSEvent>,
+        topics: &mut [Topic],
+        updates: &mut mpsc::UnboundedReceiver<WSUpdate>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        for topic in topics.iter() {
+            self.request(&topic.request()?).await?;
+        }
+        let o = self.options;
+        let mut pings = time::interval_at(Instant::now() + o.ping_interval, o.ping_interval);
+        let check_every = o.ack_timeout.min(o.stale_after) / 4;
+        let mut checks = time::interval_at(Instant::now() + check_every, check_every);
+        loop {
+            tokio::select! {
+                msg = self.read.next() => match msg {
+                    Some(msg) => self.received(msg?, sender).await?,
+                    None => return Err("ws: connection ended".into()),
+                },
+                _ = pings.tick() => {
+                    //println!("ping!");
+                    self.request(&WSPingReq { action: String::from("ping") }).await?;
+                }
+                Some(update) = updates.recv() => {
+                    // applied first so a reconnect picks it up if sending fails
+                    if update.apply_to(topics) {
+                        self.request(&update.request()).await?;
+                    }
+                }
+                _ = checks.tick() => self.check()?,
+            }
+        }
+    }
+
+    // sends `req` asking for an ack
+    async fn request<T: Serialize>(&mut self, req: &T) -> Result<(), Box<dyn std::error::Error>> {
+        let mut req = serde_json::to_value(req)?;
+        self.last_id += 1;
+        let id = self.last_id.to_string();
+        let action = String::from(req["action"].as_str().unwrap_or_default());
+        let due = Instant::now() + self.options.ack_timeout;
+        self.pending.insert(id.clone(), (action, due));
+        req["ack"] = Value::Bool(true);
+        req["id"] = Value::String(id);
+        self.write.send(Message::text(req.to_string())).await?;
+        Ok(())
+    }
+
+    async fn received(
+        &mut self,
+        msg: Message,
+        sender: &mpsc::Sender<WSEvent>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.last_heard = Instant::now();
+        if !msg.is_text() {
+            return Ok(());
+        }
+        let nr: WSNanoResp = serde_json::from_str(msg.into_text()?.as_str())?;
+        if nr.ack.is_some() {
+            if let Some(id) = nr.id {
+                self.pending.remove(&id);
+            }
+            // the first acks are for the subscriptions
+            if !self.subscribed && self.pending.values().all(|(a, _)| a != "subscribe") {
+                self.subscribed = true;
+                if self.reconnect {
+                    sender.send(WSEvent::Reconnected).await?;
+                }
+            }
+            return Ok(());
+        }
+        let (topic, message) = match (nr.topic, nr.message) {
+            (Some(topic), Some(message)) => (topic, message),
+            _ => return Ok(()),
+        };
+        let message = match TopicMessage::parse(&topic, message) {
+            Ok(Some(m)) => m,
+            Ok(None) => return Ok(()),
+            // one odd message is no reason to drop the connection
+            Err(e) => {
+                let bad = format!("ws: bad {} message: {}", topic, e);
+                sender.send(WSEvent::Error(bad)).await?;
+                return Ok(());
+            }
+        };
+        //println!("\n\nsend conf:\n\n{:#?}", message);
+        let n = Notification {
+            time: nr.time,
+            message,
+        };
+        sender.send(WSEvent::Notification(Box::new(n))).await?;
+        Ok(())
+    }
+
+    // errors when the node is overdue with an ack or went quiet
+    fn check(&self) -> Result<(), String> {
+        let now = Instant::now();
+        if let Some((action, _)) = self.pending.values().find(|(_, due)| *due <= now) {
+            return Err(format!(
+                "no ack for {} within {:?}",
+                action, self.options.ack_timeout
+            ));
+        }
+        if now.duration_since(self.last_heard) >= self.options.stale_after {
+            return Err(format!(
+                "nothing from the node for {:?}",
+                self.options.stale_after
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn topic_requests() {
+        let options = ConfirmationOptions {
+            confirmation_type: Some(ConfirmationType::ActiveQuorum),
+            include_election_info: Some(true),
+            ..ConfirmationOptions::accounts(vec![String::from("nano_1")])
+        };
+        let req = Topic::Confirmation(options).request().unwrap();
+        assert_eq!(
+            serde_json::to_value(&req).unwrap(),
+            json!({
+                "action": "subscribe",
+                "topic": "confirmation",
+                "options": {
+                    "accounts": ["nano_1"],
+                    "confirmation_type": "active_quorum",
+                    "include_election_info": true,
+                },
+            })
+        );
+        let req = Topic::ActiveDifficulty.request().unwrap();
+        assert_eq!(
+            serde_json::to_value(&req).unwrap(),
+            json!({ "action": "subscribe", "topic": "active_difficulty" })
+        );
+        assert_eq!(Topic::Votes(VotesOptions::default()).name(), "vote");
+    }
+
+    #[test]
+    fn parses_topic_messages() {
+        let m = TopicMessage::parse(
+            "vote",
+            json!({
+                "account": "nano_1", "signature": "AB", "sequence": "1",
+                "blocks": ["CD"], "type": "replay",
+            }),
+        )
+        .unwrap();
+        assert!(matches!(m, Some(TopicMessage::Vote(v)) if v.kind == "replay"));
+        let m = TopicMessage::parse(
+            "confirmation",
+            json!({
+                "account": "nano_1", "amount": "1", "hash": "AB",
+                "confirmation_type": "active_quorum",
+                "election_info": {
+                    "duration": "546", "time": "1", "tally": "2", "blocks": "1",
+                    "voters": "3", "request_count": "1",
+                },
+            }),
+        )
+        .unwrap();
+        match m {
+            Some(TopicMessage::Confirmation(c)) => {
+                assert!(c.block.is_none());
+                assert_eq!(c.election_info.unwrap().voters, "3");
+            }
+            m => panic!("{:?}", m),
+        }
+        let m = TopicMessage::parse(
+            "work",
+            json!({
+                "success": "false", "reason": "cancelled", "duration": "1",
+                "request": { "hash": "AB", "difficulty": "ff", "multiplier": "1" },
+                "result": {}, "bad_peers": "",
+            }),
+        )
+        .unwrap();
+        // the node sends "" for no bad peers
+        match m {
+            Some(TopicMessage::Work(w)) => {
+                assert!(w.bad_peers.is_empty());
+                assert!(w.result.work.is_empty());
+            }
+            m => panic!("{:?}", m),
+        }
+        assert!(TopicMessage::parse("new", json!({})).unwrap().is_none());
+    }
+
+    #[test]
+    fn updates_only_filtered_topics() {
+        let mut topics = vec![Topic::Votes(VotesOptions::default())];
+        assert!(!WSUpdate::Add(vec![String::from("nano_1")]).apply_to(&mut topics));
+        topics.push(Topic::Confirmation(ConfirmationOptions::accounts(vec![])));
+        assert!(WSUpdate::Add(vec![String::from("nano_1")]).apply_to(&mut topics));
+        assert_eq!(
+            topics[1],
+            Topic::Confirmation(ConfirmationOptions::accounts(vec![String::from("nano_1")]))
+        );
     }
 }
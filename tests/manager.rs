@@ -0,0 +1,730 @@
+// end to end Manager tests against nanors::mock_node, with canned responses or a
+// simulated ledger
+use bigdecimal::BigDecimal;
+use nanors::account::Account;
+use nanors::config::{ManagerConfig, WorkSource};
+use nanors::ledger::Ledger;
+use nanors::manager::{ConfirmError, ConfirmOptions, Event, Manager};
+use nanors::mock_node::MockNode;
+use nanors::network::Network;
+use nanors::raw::Raw;
+use nanors::wallet::Wallet;
+use serde_json::{json, Value};
+use std::convert::TryInto;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+const SEED: [u8; 32] = [
+    137, 197, 104, 229, 75, 120, 185, 178, 9, 190, 248, 22, 140, 246, 140, 143, 247, 174, 97, 154,
+    204, 80, 167, 39, 121, 67, 35, 190, 48, 60, 244, 11,
+];
+const OPENED: &str = "nano_1e69ju7uc6eu3zkgm3krmu9x7hejdnx8sgkaah3ywo5xws6ttcy1g4yeo4bi";
+const UNOPENED: &str = "nano_1xc8qp4qod9jaj5ooqtwjeq5qf4pnmnincqcqyctkuqfranft5j7narjhrwr";
+// public key of OPENED, the link of sends to it
+const OPENED_PK: &str = "30878ECBB5119B0FE4E986589ECFD2BD915D3A6CBA4843C3EE547DE649AD2BC0";
+const REP: &str = "nano_16u1uufyoig8777y6r8iqjtrw8sg8maqrm36zzcm95jmbd9i9aj5i8abr8u5";
+const ONE_NANO: &str = "1000000000000000000000000000000";
+const TWO_NANO: &str = "2000000000000000000000000000000";
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+// OPENED holds 2 nano with nothing receivable, UNOPENED has 1 nano waiting
+async fn mock_node() -> MockNode {
+    let node = MockNode::start().await.unwrap();
+    let frontier = "AB".repeat(32);
+    node.respond(
+        "accounts_frontiers",
+        json!({ "frontiers": { OPENED: frontier } }),
+    );
+    node.respond(
+        "accounts_balances",
+        json!({ "balances": {
+            OPENED: { "balance": TWO_NANO, "pending": "0" },
+            UNOPENED: { "balance": "0", "pending": ONE_NANO },
+        }}),
+    );
+    node.respond(
+        "blocks_info",
+        json!({ "blocks": { frontier.clone(): {
+            "block_account": OPENED, "amount": TWO_NANO, "balance": TWO_NANO, "height": "1",
+            "local_timestamp": "0", "confirmed": "true", "subtype": "open",
+            "contents": {
+                "type": "state", "account": OPENED, "previous": "0".repeat(64),
+                "representative": REP, "balance": TWO_NANO, "link": "CD".repeat(32),
+                "work": "0000000000000000",
+            },
+        }}}),
+    );
+    node.respond(
+        "accounts_receivable",
+        json!({ "blocks": {
+            OPENED: "",
+            UNOPENED: { "01".repeat(32): { "amount": ONE_NANO, "source": OPENED } },
+        }}),
+    );
+    node.respond("telemetry", telemetry());
+    // the node hashes what it is given, so echo the block's own hash back
+    node.respond_with("process", |r| json!({ "hash": r["block"]["hash"] }));
+    node
+}
+
+fn telemetry() -> Value {
+    serde_json::from_str(include_str!("fixtures/rpc/telemetry.json")).unwrap()
+}
+
+async fn manager(node: &MockNode) -> Manager {
+    manager_with_work(node, WorkSource::Local).await
+}
+
+async fn manager_with_work(node: &MockNode, work: WorkSource) -> Manager {
+    let mut config = ManagerConfig::new(Network::Dev);
+    config.work = work;
+    manager_with_config(node, config).await
+}
+
+async fn manager_with_config(node: &MockNode, mut config: ManagerConfig) -> Manager {
+    config.rpc_url = String::from(node.rpc_url());
+    config.ws_url = String::from(node.ws_url());
+    let mut m = Manager::new(config).await.unwrap();
+    let accounts = (0..2)
+        .map(|i| Account::new(i, &SEED, Network::Dev).unwrap())
+        .collect();
+    // built directly so the test does not touch the wallet file
+    let wallet = Wallet {
+        name: String::from("test"),
+        network: Network::Dev,
+        accounts: nanors::wallet::accounts(accounts),
+    };
+    m.set_wallet(wallet).await.unwrap();
+    m
+}
+
+async fn wait_for_balance(m: &Manager, addr: &str, mnano: u32) {
+    let received = async {
+        while balance(m, addr).await != BigDecimal::from(mnano) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+    tokio::time::timeout(TIMEOUT, received).await.unwrap();
+}
+
+async fn balance(m: &Manager, addr: &str) -> BigDecimal {
+    let info = m.get_accounts_info().await;
+    info.into_iter()
+        .find(|a| a.addr == addr)
+        .unwrap()
+        .balance_mnano
+}
+
+// skips ahead to the first event `f` picks out
+async fn wait_event<F, T>(events: &mut broadcast::Receiver<Event>, f: F) -> T
+where
+    F: Fn(Event) -> Option<T>,
+{
+    let waiting = async {
+        loop {
+            if let Some(found) = f(events.recv().await.unwrap()) {
+                return found;
+            }
+        }
+    };
+    tokio::time::timeout(TIMEOUT, waiting).await.unwrap()
+}
+
+fn processed(node: &MockNode, subtype: &str) -> Vec<Value> {
+    node.requests("process")
+        .into_iter()
+        .filter(|r| r["subtype"] == subtype)
+        .collect()
+}
+
+#[tokio::test]
+async fn set_wallet_synchronizes_and_subscribes() {
+    let node = mock_node().await;
+    let m = manager(&node).await;
+    assert_eq!(balance(&m, OPENED).await, BigDecimal::from(2));
+    // the receivable send was opened with the default representative
+    let open = &processed(&node, "open")[0]["block"];
+    assert_eq!(open["account"], UNOPENED);
+    assert_eq!(open["link"], "01".repeat(32));
+    assert_eq!(open["representative"], nanors::account::DEFAULT_REP);
+    assert_eq!(balance(&m, UNOPENED).await, BigDecimal::from(1));
+    node.wait_subscribed(OPENED, TIMEOUT).await.unwrap();
+    assert!(node.subscribed(UNOPENED));
+}
+
+#[tokio::test]
+async fn sends_and_changes_representative() {
+    let node = mock_node().await;
+    let m = manager(&node).await;
+    let hash = m.send(BigDecimal::from(1), OPENED, UNOPENED).await.unwrap();
+    let send = &processed(&node, "send")[0]["block"];
+    assert_eq!(send["hash"], hash.as_str());
+    assert_eq!(send["previous"], "AB".repeat(32));
+    assert_eq!(send["balance"], ONE_NANO);
+    assert_eq!(balance(&m, OPENED).await, BigDecimal::from(1));
+
+    let hash = m
+        .change(OPENED, nanors::account::DEFAULT_REP)
+        .await
+        .unwrap();
+    let change = &processed(&node, "change")[0]["block"];
+    assert_eq!(change["previous"], send["hash"]);
+    assert_eq!(change["hash"], hash.as_str());
+    assert_eq!(change["representative"], nanors::account::DEFAULT_REP);
+}
+
+#[tokio::test]
+async fn loads_the_balance_of_the_frontier_block() {
+    let node = mock_node().await;
+    // the frontier is not confirmed yet, so the confirmed balance is from before it
+    node.respond(
+        "accounts_balances",
+        json!({ "balances": {
+            OPENED: { "balance": ONE_NANO, "pending": "0" },
+            UNOPENED: { "balance": "0", "pending": ONE_NANO },
+        }}),
+    );
+    let m = manager(&node).await;
+    assert_eq!(balance(&m, OPENED).await, BigDecimal::from(2));
+    m.send(BigDecimal::from(1), OPENED, UNOPENED).await.unwrap();
+    let send = &processed(&node, "send")[0]["block"];
+    assert_eq!(send["previous"], "AB".repeat(32));
+    assert_eq!(send["balance"], ONE_NANO);
+}
+
+#[tokio::test]
+async fn generates_work_locally_when_the_provider_sends_garbage() {
+    let node = mock_node().await;
+    node.respond("work_generate", json!({ "work": "not work" }));
+    let provider = MockNode::start().await.unwrap();
+    provider.respond("work_generate", json!({ "work": "not work either" }));
+    let mut config = ManagerConfig::new(Network::Dev);
+    config.work = WorkSource::Node;
+    config.work_url = Some(String::from(provider.rpc_url()));
+    let m = manager_with_config(&node, config).await;
+    let mut events = m.events();
+    let asked = || {
+        let provider = provider.requests("work_generate").len();
+        (provider, node.requests("work_generate").len())
+    };
+    let before = asked();
+    m.send(BigDecimal::from(1), OPENED, UNOPENED).await.unwrap();
+    let send = &processed(&node, "send")[0]["block"];
+    assert_ne!(send["work"], "not work");
+    // the work provider first, then the rpc node before giving up on both
+    let after = asked();
+    assert_eq!((after.0 - before.0, after.1 - before.1), (1, 1));
+    let rejected = wait_event(&mut events, |e| match e {
+        Event::Warning(w) => Some(w),
+        _ => None,
+    })
+    .await;
+    assert!(rejected.contains("rejected invalid work not work either"));
+    assert!(rejected.contains(provider.rpc_url()));
+    let rejected = wait_event(&mut events, |e| match e {
+        Event::Warning(w) => Some(w),
+        _ => None,
+    })
+    .await;
+    assert!(rejected.contains(node.rpc_url()));
+    wait_event(&mut events, |e| match e {
+        Event::Warning(w) if w.contains("generating it locally") => Some(()),
+        _ => None,
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn node_errors_surface_from_send() {
+    let node = mock_node().await;
+    let m = manager(&node).await;
+    node.respond("process", json!({ "error": "Fork" }));
+    let err = m
+        .send(BigDecimal::from(1), OPENED, UNOPENED)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("Fork"));
+    assert_eq!(balance(&m, OPENED).await, BigDecimal::from(2));
+}
+
+#[tokio::test]
+async fn receives_confirmed_sends() {
+    let node = mock_node().await;
+    let m = manager(&node).await;
+    node.wait_subscribed(OPENED, TIMEOUT).await.unwrap();
+    let sender = Account::new(5, &SEED, Network::Dev).unwrap().addr;
+    let hash = "04".repeat(32);
+    let delivered = node.confirm(json!({
+        "account": sender,
+        "amount": ONE_NANO,
+        "hash": hash,
+        "block": {
+            "type": "state", "account": sender, "previous": "05".repeat(32),
+            "representative": REP, "balance": "0", "link": OPENED_PK,
+            "link_as_account": OPENED, "signature": "0".repeat(128),
+            "subtype": "send", "work": "0000000000000000",
+        },
+    }));
+    assert_eq!(delivered, 1);
+    wait_for_balance(&m, OPENED, 3).await;
+    let receive = &processed(&node, "receive")[0]["block"];
+    assert_eq!(receive["link"], hash);
+}
+
+#[tokio::test]
+async fn keeps_receiving_after_a_bad_epoch_block() {
+    let node = MockNode::start().await.unwrap();
+    let ledger = node.serve_ledger(Ledger::new().unwrap());
+    let one: Raw = ONE_NANO.parse().unwrap();
+    ledger.lock().unwrap().fund(OPENED, one).unwrap();
+    let m = manager(&node).await;
+    let mut events = m.events();
+    node.wait_subscribed(OPENED, TIMEOUT).await.unwrap();
+    // not on our frontier and with no epoch link
+    let hash = "06".repeat(32);
+    let delivered = node.confirm(json!({
+        "account": OPENED,
+        "amount": "0",
+        "hash": hash,
+        "block": {
+            "type": "state", "account": OPENED, "previous": "07".repeat(32),
+            "representative": REP, "balance": ONE_NANO, "link": "0".repeat(64),
+            "signature": "0".repeat(128), "subtype": "epoch", "work": "0000000000000000",
+        },
+    }));
+    assert_eq!(delivered, 1);
+    let err = wait_event(&mut events, |e| match e {
+        Event::Error(e) => Some(e),
+        _ => None,
+    })
+    .await;
+    assert!(err.contains(&hash));
+
+    // the confirmation feed is still going
+    ledger.lock().unwrap().fund(OPENED, one).unwrap();
+    wait_for_balance(&m, OPENED, 2).await;
+}
+
+#[tokio::test]
+async fn keeps_receiving_after_a_malformed_confirmation() {
+    let node = MockNode::start().await.unwrap();
+    let ledger = node.serve_ledger(Ledger::new().unwrap());
+    let one: Raw = ONE_NANO.parse().unwrap();
+    ledger.lock().unwrap().fund(OPENED, one).unwrap();
+    let m = manager(&node).await;
+    let mut events = m.events();
+    node.wait_subscribed(OPENED, TIMEOUT).await.unwrap();
+    let hash = "09".repeat(32);
+    let delivered = node.confirm(json!({
+        "account": OPENED,
+        "amount": "not a number",
+        "hash": hash,
+        "block": {
+            "type": "state", "account": OPENED, "previous": "07".repeat(32),
+            "representative": REP, "balance": "0", "link": "0".repeat(64),
+            "signature": "0".repeat(128), "subtype": "send", "work": "0000000000000000",
+        },
+    }));
+    assert_eq!(delivered, 1);
+    let err = wait_event(&mut events, |e| match e {
+        Event::Error(e) => Some(e),
+        _ => None,
+    })
+    .await;
+    assert!(err.contains(&hash));
+
+    // the confirmation feed is still going
+    ledger.lock().unwrap().fund(OPENED, one).unwrap();
+    wait_for_balance(&m, OPENED, 2).await;
+}
+
+#[tokio::test]
+async fn keeps_receiving_after_a_send_without_link_as_account() {
+    let node = MockNode::start().await.unwrap();
+    let ledger = node.serve_ledger(Ledger::new().unwrap());
+    let one: Raw = ONE_NANO.parse().unwrap();
+    ledger.lock().unwrap().fund(OPENED, one).unwrap();
+    let m = manager(&node).await;
+    let mut events = m.events();
+    node.wait_subscribed(OPENED, TIMEOUT).await.unwrap();
+    let hash = "08".repeat(32);
+    let delivered = node.confirm(json!({
+        "account": OPENED,
+        "amount": ONE_NANO,
+        "hash": hash,
+        "block": {
+            "type": "state", "account": OPENED, "previous": "07".repeat(32),
+            "representative": REP, "balance": "0", "link": "0".repeat(64),
+            "signature": "0".repeat(128), "subtype": "send", "work": "0000000000000000",
+        },
+    }));
+    assert_eq!(delivered, 1);
+    let warning = wait_event(&mut events, |e| match e {
+        Event::Warning(w) => Some(w),
+        _ => None,
+    })
+    .await;
+    assert!(warning.contains(&hash));
+
+    // the confirmation feed is still going
+    ledger.lock().unwrap().fund(OPENED, one).unwrap();
+    wait_for_balance(&m, OPENED, 2).await;
+}
+
+#[tokio::test]
+async fn serves_recorded_fixtures() {
+    let node = MockNode::start().await.unwrap();
+    node.respond_from_dir("tests/fixtures/rpc").unwrap();
+    let rpc = nanors::rpc::ClientRpc::new(node.rpc_url()).unwrap();
+    assert_eq!(rpc.block_count().await.unwrap().count, "1000");
+    assert_eq!(node.requests("block_count").len(), 1);
+    // no fixture for it
+    assert!(rpc.work_generate(&"0".repeat(64), "ff").await.is_err());
+}
+
+#[tokio::test]
+async fn moves_funds_on_a_simulated_ledger() {
+    let node = MockNode::start().await.unwrap();
+    let ledger = node.serve_ledger(Ledger::new().unwrap());
+    let three: Raw = "3000000000000000000000000000000".parse().unwrap();
+    ledger.lock().unwrap().fund(OPENED, three).unwrap();
+    let m = manager(&node).await;
+    // synchronizing opened the account with the funding send
+    assert_eq!(balance(&m, OPENED).await, BigDecimal::from(3));
+    node.wait_subscribed(UNOPENED, TIMEOUT).await.unwrap();
+
+    m.send(BigDecimal::from(1), OPENED, UNOPENED).await.unwrap();
+    // the ledger confirms the send over the websocket and the manager receives it
+    wait_for_balance(&m, UNOPENED, 1).await;
+    m.change(UNOPENED, REP).await.unwrap();
+
+    let ledger = ledger.lock().unwrap();
+    assert_eq!(ledger.balance(OPENED).to_mnano(), BigDecimal::from(2));
+    assert_eq!(ledger.balance(UNOPENED).to_mnano(), BigDecimal::from(1));
+    assert!(ledger.receivable(UNOPENED).is_empty());
+    assert_eq!(ledger.representative(UNOPENED), Some(REP));
+}
+
+#[tokio::test]
+async fn handle_runs_commands_from_several_tasks() {
+    let node = MockNode::start().await.unwrap();
+    let ledger = node.serve_ledger(Ledger::new().unwrap());
+    let one: Raw = ONE_NANO.parse().unwrap();
+    let three: Raw = "3000000000000000000000000000000".parse().unwrap();
+    ledger.lock().unwrap().fund(OPENED, three).unwrap();
+    // nothing is confirmed over the websocket, so receiving is up to us
+    node.mute(true);
+    let handle = manager(&node).await.spawn();
+    ledger.lock().unwrap().fund(UNOPENED, one).unwrap();
+
+    let elsewhere = Account::new(5, &SEED, Network::Dev).unwrap().addr;
+    let sender = handle.clone();
+    let sent = tokio::spawn(async move {
+        let sent = sender.send(BigDecimal::from(1), OPENED, &elsewhere).await;
+        sent.map_err(|e| e.to_string())
+    });
+    let receiver = handle.clone();
+    let received = tokio::spawn(async move {
+        let received = receiver.receive(UNOPENED).await;
+        received.map_err(|e| e.to_string())
+    });
+    sent.await.unwrap().unwrap();
+    assert_eq!(received.await.unwrap().unwrap().len(), 1);
+
+    let info = handle.get_accounts_info().await.unwrap();
+    assert_eq!(info[0].balance_mnano, BigDecimal::from(2));
+    assert_eq!(info[1].balance_mnano, BigDecimal::from(1));
+    assert!(ledger.lock().unwrap().receivable(UNOPENED).is_empty());
+}
+
+#[tokio::test]
+async fn handle_shutdown_closes_the_websocket() {
+    let node = mock_node().await;
+    let handle = manager(&node).await.spawn();
+    node.wait_subscribed(OPENED, TIMEOUT).await.unwrap();
+    handle.shutdown().await;
+    let closed = async {
+        while node.subscribed(OPENED) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+    tokio::time::timeout(TIMEOUT, closed).await.unwrap();
+    let err = handle.get_accounts_info().await.unwrap_err();
+    assert_eq!(err.to_string(), "manager has shut down");
+    // shutting down twice is fine
+    handle.shutdown().await;
+}
+
+#[tokio::test]
+async fn reports_what_happens_to_the_accounts() {
+    let node = MockNode::start().await.unwrap();
+    let ledger = node.serve_ledger(Ledger::new().unwrap());
+    let three: Raw = "3000000000000000000000000000000".parse().unwrap();
+    ledger.lock().unwrap().fund(OPENED, three).unwrap();
+    let m = manager(&node).await;
+    let mut events = m.events();
+    node.wait_subscribed(UNOPENED, TIMEOUT).await.unwrap();
+
+    let sent = m.send(BigDecimal::from(1), OPENED, UNOPENED).await.unwrap();
+    let hash = wait_event(&mut events, |e| match e {
+        Event::Sent { account, hash, .. } if account == OPENED => Some(hash),
+        _ => None,
+    })
+    .await;
+    assert_eq!(hash, sent);
+    let confirmed = wait_event(&mut events, |e| match e {
+        Event::Confirmed { hash, .. } => Some(hash),
+        _ => None,
+    })
+    .await;
+    assert_eq!(confirmed, sent);
+    let (account, send) = wait_event(&mut events, |e| match e {
+        Event::IncomingPending { account, send, .. } => Some((account, send)),
+        _ => None,
+    })
+    .await;
+    assert_eq!((account.as_str(), send.as_str()), (UNOPENED, sent.as_str()));
+    let (send, amount) = wait_event(&mut events, |e| match e {
+        Event::Received { send, amount, .. } => Some((send, amount)),
+        _ => None,
+    })
+    .await;
+    assert_eq!((send, amount.to_mnano()), (sent, BigDecimal::from(1)));
+
+    node.disconnect_all();
+    wait_event(&mut events, |e| {
+        matches!(e, Event::ConnectionLost(_)).then(|| ())
+    })
+    .await;
+    wait_event(&mut events, |e| {
+        matches!(e, Event::ConnectionRestored).then(|| ())
+    })
+    .await;
+    wait_event(&mut events, |e| matches!(e, Event::SyncStarted).then(|| ())).await;
+    wait_event(&mut events, |e| {
+        matches!(e, Event::SyncFinished).then(|| ())
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn rescans_after_reconnecting() {
+    let node = MockNode::start().await.unwrap();
+    let ledger = node.serve_ledger(Ledger::new().unwrap());
+    let one: Raw = ONE_NANO.parse().unwrap();
+    ledger.lock().unwrap().fund(OPENED, one).unwrap();
+    let m = manager(&node).await;
+    node.wait_subscribed(UNOPENED, TIMEOUT).await.unwrap();
+
+    // the confirmation for this send goes nowhere while the websocket is down
+    node.disconnect_all();
+    ledger.lock().unwrap().fund(UNOPENED, one).unwrap();
+    // the manager resubscribes and finds the receivable send when it rescans
+    wait_for_balance(&m, UNOPENED, 1).await;
+    assert!(node.subscribed(OPENED) && node.subscribed(UNOPENED));
+    assert!(ledger.lock().unwrap().receivable(UNOPENED).is_empty());
+}
+
+#[tokio::test]
+async fn confirms_sends_over_the_websocket() {
+    let node = MockNode::start().await.unwrap();
+    let ledger = node.serve_ledger(Ledger::new().unwrap());
+    let three: Raw = "3000000000000000000000000000000".parse().unwrap();
+    ledger.lock().unwrap().fund(OPENED, three).unwrap();
+    let m = manager(&node).await;
+    node.wait_subscribed(OPENED, TIMEOUT).await.unwrap();
+    // the websocket has to do it
+    node.respond("block_info", json!({ "error": "Block not found" }));
+    let hash = m.send(BigDecimal::from(1), OPENED, UNOPENED).await.unwrap();
+    // the ledger confirmed it right away, possibly before we started waiting
+    let options = ConfirmOptions::default();
+    tokio::time::timeout(TIMEOUT, m.confirmation(&hash, options))
+        .await
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn asks_for_an_election_when_confirmation_stalls() {
+    let node = mock_node().await;
+    let m = manager(&node).await;
+    let hash = m.send(BigDecimal::from(1), OPENED, UNOPENED).await.unwrap();
+    let started = Arc::new(AtomicBool::new(false));
+    let s = started.clone();
+    node.respond_with("block_confirm", move |_| {
+        s.store(true, Ordering::SeqCst);
+        json!({ "started": "1" })
+    });
+    // confirmed once the election ran
+    node.respond_with("block_info", move |r| {
+        let confirmed = started.load(Ordering::SeqCst).to_string();
+        json!({
+            "block_account": OPENED, "amount": ONE_NANO, "balance": ONE_NANO,
+            "height": "2", "local_timestamp": "0", "confirmed": confirmed,
+            "subtype": "send", "contents": {
+                "type": "state", "account": OPENED, "previous": "AB".repeat(32),
+                "representative": REP, "balance": ONE_NANO, "link": r["hash"],
+                "work": "0000000000000000",
+            },
+        })
+    });
+    let options = ConfirmOptions {
+        timeout: TIMEOUT,
+        poll_interval: Duration::from_millis(20),
+        block_confirm_after: Duration::from_millis(100),
+    };
+    m.confirmation(&hash, options).await.unwrap();
+    assert_eq!(node.requests("block_confirm")[0]["hash"], hash.as_str());
+    assert!(node.requests("block_info").len() > 1);
+}
+
+#[tokio::test]
+async fn confirmation_times_out() {
+    let node = mock_node().await;
+    let m = manager(&node).await;
+    node.respond("block_info", json!({ "error": "Block not found" }));
+    let options = ConfirmOptions {
+        timeout: Duration::from_millis(200),
+        poll_interval: Duration::from_millis(20),
+        block_confirm_after: TIMEOUT,
+    };
+    let hash = "06".repeat(32);
+    let err = m.confirmation(&hash, options).await.unwrap_err();
+    assert!(matches!(err, ConfirmError::Timeout { hash: h, .. } if h == hash));
+    assert!(node.requests("block_confirm").is_empty());
+}
+
+#[tokio::test]
+async fn rebuilds_blocks_on_the_real_frontier_after_a_fork() {
+    let node = MockNode::start().await.unwrap();
+    let ledger = node.serve_ledger(Ledger::new().unwrap());
+    let three: Raw = "3000000000000000000000000000000".parse().unwrap();
+    ledger.lock().unwrap().fund(OPENED, three).unwrap();
+    let m = manager(&node).await;
+    node.wait_subscribed(OPENED, TIMEOUT).await.unwrap();
+    // no confirmations from here on, so only the failed publish can tell
+    node.mute(true);
+    node.disconnect_all();
+
+    // the same seed used elsewhere moves the account on
+    let mut elsewhere = Account::new(0, &SEED, Network::Dev).unwrap();
+    let frontier = String::from(ledger.lock().unwrap().frontier(OPENED).unwrap());
+    elsewhere.load(three, frontier.clone(), String::from(REP));
+    let threshold = hex::decode(Network::Dev.send_difficulty()).unwrap();
+    let work = nanors::work::pow_local(elsewhere.frontier, &threshold.try_into().unwrap());
+    elsewhere.cache_work(hex::encode(work.unwrap()));
+    let block = elsewhere.send(ONE_NANO.parse().unwrap(), UNOPENED).unwrap();
+    ledger.lock().unwrap().process(&block).unwrap();
+
+    m.send(BigDecimal::from(1), OPENED, UNOPENED).await.unwrap();
+    let sends = processed(&node, "send");
+    assert_eq!(sends.len(), 2);
+    assert_eq!(sends[0]["block"]["previous"], frontier.as_str());
+    assert_eq!(sends[1]["block"]["previous"], block.hash.unwrap().as_str());
+    assert_eq!(balance(&m, OPENED).await, BigDecimal::from(1));
+    assert_eq!(
+        ledger.lock().unwrap().balance(OPENED).to_mnano(),
+        BigDecimal::from(1)
+    );
+}
+
+#[tokio::test]
+async fn reports_forks_on_blocks_sent_before_switching_nodes() {
+    let node = mock_node().await;
+    let mut m = manager(&node).await;
+    node.wait_subscribed(OPENED, TIMEOUT).await.unwrap();
+    let ours = m.send(BigDecimal::from(1), OPENED, UNOPENED).await.unwrap();
+    m.set_node(node.rpc_url(), node.ws_url()).await.unwrap();
+    let switched = async {
+        while node.connections() < 2 || node.open_connections() != 1 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+    tokio::time::timeout(TIMEOUT, switched).await.unwrap();
+    node.wait_subscribed(OPENED, TIMEOUT).await.unwrap();
+
+    let theirs = "07".repeat(32);
+    node.confirm(json!({
+        "account": OPENED,
+        "amount": ONE_NANO,
+        "hash": theirs,
+        "block": {
+            "type": "state", "account": OPENED, "previous": "AB".repeat(32),
+            "representative": REP, "balance": ONE_NANO, "link": "CD".repeat(32),
+            "link_as_account": REP, "signature": "0".repeat(128),
+            "subtype": "send", "work": "0000000000000000",
+        },
+    }));
+    let err = tokio::time::timeout(TIMEOUT, m.confirmation(&ours, ConfirmOptions::default()))
+        .await
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ConfirmError::Replaced {
+            hash: ours,
+            by: theirs
+        }
+    );
+}
+
+#[tokio::test]
+async fn reports_blocks_replaced_by_a_fork() {
+    let node = mock_node().await;
+    let m = manager(&node).await;
+    node.wait_subscribed(OPENED, TIMEOUT).await.unwrap();
+    let ours = m.send(BigDecimal::from(1), OPENED, UNOPENED).await.unwrap();
+    assert_eq!(balance(&m, OPENED).await, BigDecimal::from(1));
+
+    // a competing send on the same previous wins
+    let theirs = "07".repeat(32);
+    node.respond(
+        "account_info",
+        json!({
+            "frontier": theirs, "open_block": "CD".repeat(32),
+            "representative_block": "CD".repeat(32), "representative": REP,
+            "balance": ONE_NANO, "modified_timestamp": "0", "block_count": "2",
+            "account_version": "2", "confirmation_height": "2",
+            "confirmation_height_frontier": theirs,
+        }),
+    );
+    node.confirm(json!({
+        "account": OPENED,
+        "amount": ONE_NANO,
+        "hash": theirs,
+        "block": {
+            "type": "state", "account": OPENED, "previous": "AB".repeat(32),
+            "representative": REP, "balance": ONE_NANO, "link": "CD".repeat(32),
+            "link_as_account": REP, "signature": "0".repeat(128),
+            "subtype": "send", "work": "0000000000000000",
+        },
+    }));
+    let err = tokio::time::timeout(TIMEOUT, m.confirmation(&ours, ConfirmOptions::default()))
+        .await
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ConfirmError::Replaced {
+            hash: ours,
+            by: theirs.clone()
+        }
+    );
+    // reloaded from the node
+    let reloaded = async {
+        while !node
+            .requests("account_info")
+            .iter()
+            .any(|r| r["account"] == OPENED)
+        {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+    tokio::time::timeout(TIMEOUT, reloaded).await.unwrap();
+    let hash = m.change(OPENED, REP).await.unwrap();
+    let change = processed(&node, "change").pop().unwrap();
+    assert_eq!(change["block"]["hash"], hash.as_str());
+    assert_eq!(change["block"]["previous"], theirs);
+}
@@ -0,0 +1,175 @@
+// confirmation subscriptions against nanors::mock_node
+use futures::{FutureExt, StreamExt};
+use nanors::mock_node::MockNode;
+use nanors::ws::{self, SubscriptionHandle, Topic, TopicMessage, WSEvent, WSOptions};
+use serde_json::json;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+const FIRST: &str = "nano_1e69ju7uc6eu3zkgm3krmu9x7hejdnx8sgkaah3ywo5xws6ttcy1g4yeo4bi";
+const SECOND: &str = "nano_1xc8qp4qod9jaj5ooqtwjeq5qf4pnmnincqcqyctkuqfranft5j7narjhrwr";
+const TIMEOUT: Duration = Duration::from_secs(10);
+const QUICK: WSOptions = WSOptions {
+    ping_interval: Duration::from_millis(100),
+    ack_timeout: Duration::from_millis(300),
+    stale_after: Duration::from_millis(500),
+};
+
+fn subscribe(
+    node: &MockNode,
+    accounts: &[&str],
+    options: WSOptions,
+) -> (SubscriptionHandle, mpsc::Receiver<WSEvent>) {
+    let (handle, updates) = SubscriptionHandle::new();
+    let (tx, rx) = mpsc::channel(20);
+    let url = String::from(node.ws_url());
+    let accounts = accounts.iter().map(|a| String::from(*a)).collect();
+    tokio::spawn(async move {
+        ws::subscribe_confirmation(&url, accounts, tx, updates, options)
+            .await
+            .unwrap();
+    });
+    (handle, rx)
+}
+
+// skips the failed connection attempts in between
+async fn next_event(rx: &mut mpsc::Receiver<WSEvent>) -> Option<WSEvent> {
+    loop {
+        match tokio::time::timeout(TIMEOUT, rx.recv()).await.unwrap() {
+            Some(WSEvent::Error(_)) => {}
+            event => return event,
+        }
+    }
+}
+
+async fn next(sub: &mut ws::Subscription) -> TopicMessage {
+    match tokio::time::timeout(TIMEOUT, sub.next()).await.unwrap() {
+        Some(WSEvent::Notification(n)) => n.message,
+        e => panic!("{:?}", e),
+    }
+}
+
+async fn wait_unsubscribed(node: &MockNode, account: &str) {
+    let waiting = async {
+        while node.subscribed(account) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+    tokio::time::timeout(TIMEOUT, waiting).await.unwrap();
+}
+
+#[tokio::test]
+async fn updates_watched_accounts() {
+    let node = MockNode::start().await.unwrap();
+    let (handle, _rx) = subscribe(&node, &[FIRST], WSOptions::default());
+    node.wait_subscribed(FIRST, TIMEOUT).await.unwrap();
+    assert!(!node.subscribed(SECOND));
+
+    handle.add_accounts(vec![String::from(SECOND)]).unwrap();
+    node.wait_subscribed(SECOND, TIMEOUT).await.unwrap();
+    handle.remove_accounts(vec![String::from(FIRST)]).unwrap();
+    wait_unsubscribed(&node, FIRST).await;
+    assert!(node.subscribed(SECOND));
+}
+
+#[tokio::test]
+async fn resubscribes_with_updated_accounts() {
+    let node = MockNode::start().await.unwrap();
+    let (handle, mut rx) = subscribe(&node, &[FIRST], WSOptions::default());
+    node.wait_subscribed(FIRST, TIMEOUT).await.unwrap();
+    node.disconnect_all();
+    // made while the connection is down
+    handle.add_accounts(vec![String::from(SECOND)]).unwrap();
+    handle.remove_accounts(vec![String::from(FIRST)]).unwrap();
+    assert!(matches!(
+        next_event(&mut rx).await,
+        Some(WSEvent::Disconnected(_))
+    ));
+    assert!(matches!(
+        next_event(&mut rx).await,
+        Some(WSEvent::Reconnected)
+    ));
+    node.wait_subscribed(SECOND, TIMEOUT).await.unwrap();
+    assert!(!node.subscribed(FIRST));
+}
+
+#[tokio::test]
+async fn streams_other_topics() {
+    let node = MockNode::start().await.unwrap();
+    let topics = vec![Topic::StoppedElection, Topic::NewUnconfirmedBlock];
+    let mut sub = ws::subscribe(node.ws_url(), topics, WSOptions::default()).unwrap();
+    let block = json!({
+        "type": "state", "account": FIRST, "previous": "0".repeat(64),
+        "representative": FIRST, "balance": "1", "link": "0".repeat(64),
+        "link_as_account": FIRST, "signature": "0".repeat(128),
+        "work": "0000000000000000", "subtype": "receive",
+    });
+    let subscribed = async {
+        // the mock only delivers once the last subscription arrived
+        while node.publish("new_unconfirmed_block", block.clone()) == 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+    tokio::time::timeout(TIMEOUT, subscribed).await.unwrap();
+    assert_eq!(node.publish("stopped_election", json!({ "hash": "AB" })), 1);
+    assert_eq!(node.publish("vote", json!({})), 0);
+
+    assert!(
+        matches!(next(&mut sub).await, TopicMessage::NewUnconfirmedBlock(b) if b.account == FIRST)
+    );
+    assert!(matches!(next(&mut sub).await, TopicMessage::StoppedElection(m) if m.hash == "AB"));
+}
+
+#[tokio::test]
+async fn reconnects_when_acks_stop() {
+    let node = MockNode::start().await.unwrap();
+    node.mute(true);
+    let (_handle, mut rx) = subscribe(&node, &[FIRST], QUICK);
+    // the subscription is never acked, so the client keeps trying
+    let retrying = async {
+        while node.connections() < 2 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+    tokio::time::timeout(TIMEOUT, retrying).await.unwrap();
+    assert!(!node.subscribed(FIRST));
+    node.mute(false);
+    node.wait_subscribed(FIRST, TIMEOUT).await.unwrap();
+    // there was no connection before, so nothing was missed. the failed attempts
+    // are reported.
+    let mut failed = 0;
+    while let Some(Some(event)) = rx.recv().now_or_never() {
+        assert!(matches!(event, WSEvent::Error(_)), "{:?}", event);
+        failed += 1;
+    }
+    assert!(failed > 0);
+}
+
+#[tokio::test]
+async fn reconnects_a_connection_gone_quiet() {
+    let node = MockNode::start().await.unwrap();
+    let (_handle, mut rx) = subscribe(&node, &[FIRST], QUICK);
+    node.wait_subscribed(FIRST, TIMEOUT).await.unwrap();
+    // pongs keep the connection alive
+    tokio::time::sleep(QUICK.stale_after * 2).await;
+    assert_eq!(node.connections(), 1);
+
+    // the socket stays open but pongs stop
+    node.mute(true);
+    let retrying = async {
+        while node.connections() < 2 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+    tokio::time::timeout(TIMEOUT, retrying).await.unwrap();
+    node.mute(false);
+    assert!(matches!(
+        next_event(&mut rx).await,
+        Some(WSEvent::Disconnected(_))
+    ));
+    assert!(matches!(
+        next_event(&mut rx).await,
+        Some(WSEvent::Reconnected)
+    ));
+    assert!(node.subscribed(FIRST));
+}